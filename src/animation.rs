@@ -1,30 +1,532 @@
+use crate::ci_status::BuildStatus;
+use crate::message_handler::EventKind;
+use crate::pattern::{Pattern, Step};
 #[cfg(target_arch = "aarch64")]
-use crate::raspberrypi_animation::RaspberryPiAnimation;
+use crate::raspberrypi_animation::RaspberryPiLed;
+use serde::{Deserialize, Deserializer, de};
+use std::collections::HashMap;
 use std::fmt::Debug;
-use std::sync::Arc;
-use std::thread;
-use std::time::Duration;
-use tracing::info;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tracing::{info, trace};
 
+const CELEBRATE: &str = "on 500ms, off 500ms, repeat 19";
+const MILESTONE: &str =
+    "fade-in 1s, fade-out 1s, repeat 3, on 100ms, off 100ms, repeat 30, on 5s, fade-out 2s";
+const SAD: &str = "fade-out 3s";
+const COMBO: &str = "on 150ms, off 150ms";
+const RELEASE: &str = "fade-in 1s, fade-out 1s, repeat 2, on 100ms, off 100ms, repeat 20";
+const PRERELEASE: &str = "fade-in 1s, fade-out 1s";
+const MERGED: &str = "fade-in 500ms, on 2s, fade-out 500ms, repeat 3";
+const ISSUE: &str = "on 300ms, off 300ms, repeat 3";
+const COMMENT: &str = "on 200ms, off 200ms, repeat 2";
+const ANSWERED: &str = "on 100ms, off 100ms, repeat 5, fade-in 1s, on 1s, fade-out 1s";
+const BUILD_PASSING: &str = "fade-in 3s, fade-out 3s, off 4s";
+const BUILD_FAILING: &str = "on 150ms, off 150ms, repeat 3, off 1s";
+const SPONSOR: &str = "fade-in 1s, on 1s, fade-out 1s, repeat 5";
+
+/// Number of brightness changes used to play a fade.
+const FADE_STEPS: u32 = 20;
+
+/// Named patterns, and which pattern each event kind plays.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AnimationSettings {
+    #[serde(
+        default = "default_patterns",
+        deserialize_with = "deserialize_patterns"
+    )]
+    patterns: HashMap<String, Pattern>,
+    #[serde(default = "default_animations")]
+    animations: HashMap<EventKind, String>,
+    /// Events arriving within this window after the first one are played as one combo.
+    #[serde(default)]
+    combo_window_ms: u64,
+    /// Maximum number of times the `combo` pattern is repeated.
+    #[serde(default = "default_combo_max")]
+    combo_max: usize,
+}
+
+fn default_combo_max() -> usize {
+    30
+}
+
+fn default_patterns() -> HashMap<String, Pattern> {
+    HashMap::from([
+        ("celebrate".to_owned(), Pattern::parse(CELEBRATE).unwrap()),
+        ("milestone".to_owned(), Pattern::parse(MILESTONE).unwrap()),
+        ("sad".to_owned(), Pattern::parse(SAD).unwrap()),
+        ("combo".to_owned(), Pattern::parse(COMBO).unwrap()),
+        ("release".to_owned(), Pattern::parse(RELEASE).unwrap()),
+        ("prerelease".to_owned(), Pattern::parse(PRERELEASE).unwrap()),
+        ("merged".to_owned(), Pattern::parse(MERGED).unwrap()),
+        ("issue".to_owned(), Pattern::parse(ISSUE).unwrap()),
+        ("comment".to_owned(), Pattern::parse(COMMENT).unwrap()),
+        ("answered".to_owned(), Pattern::parse(ANSWERED).unwrap()),
+        ("sponsor".to_owned(), Pattern::parse(SPONSOR).unwrap()),
+        (
+            "build_passing".to_owned(),
+            Pattern::parse(BUILD_PASSING).unwrap(),
+        ),
+        (
+            "build_failing".to_owned(),
+            Pattern::parse(BUILD_FAILING).unwrap(),
+        ),
+    ])
+}
+
+fn deserialize_patterns<'de, D>(deserializer: D) -> Result<HashMap<String, Pattern>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HashMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, pattern)| match Pattern::parse(&pattern) {
+            Ok(pattern) => Ok((name, pattern)),
+            Err(e) => Err(de::Error::custom(format!("pattern '{name}': {e}"))),
+        })
+        .collect()
+}
+
+fn default_animations() -> HashMap<EventKind, String> {
+    HashMap::from([
+        (EventKind::Star, "celebrate".to_owned()),
+        (EventKind::StarMilestone, "milestone".to_owned()),
+        (EventKind::Fork, "celebrate".to_owned()),
+        (EventKind::Unstar, "sad".to_owned()),
+        (EventKind::Release, "release".to_owned()),
+        (EventKind::Prerelease, "prerelease".to_owned()),
+        (EventKind::PullRequestMerged, "merged".to_owned()),
+        (EventKind::IssueOpened, "issue".to_owned()),
+        (EventKind::IssueClosed, "issue".to_owned()),
+        (EventKind::IssueLabeled, "issue".to_owned()),
+        (EventKind::IssueComment, "comment".to_owned()),
+        (EventKind::DiscussionCreated, "issue".to_owned()),
+        (EventKind::DiscussionAnswered, "comment".to_owned()),
+        (
+            EventKind::DiscussionAnsweredByCommunity,
+            "answered".to_owned(),
+        ),
+        (EventKind::DiscussionComment, "comment".to_owned()),
+        (EventKind::Push, "comment".to_owned()),
+        (EventKind::Custom, "celebrate".to_owned()),
+        (EventKind::Sponsorship, "sponsor".to_owned()),
+        (EventKind::SponsorshipTierChanged, "sponsor".to_owned()),
+        (EventKind::SponsorshipCancelled, "sad".to_owned()),
+    ])
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        Self {
+            patterns: default_patterns(),
+            animations: default_animations(),
+            combo_window_ms: 0,
+            combo_max: default_combo_max(),
+        }
+    }
+}
+
+impl AnimationSettings {
+    /// Checks that every event kind is mapped to a pattern that exists.
+    pub fn validate(&self) -> Result<(), String> {
+        for (kind, name) in &self.animations {
+            if !self.patterns.contains_key(name) {
+                return Err(format!("{kind:?} uses unknown animation pattern '{name}'"));
+            }
+        }
+        // played in a loop while idle, a pattern taking no time would never yield
+        for status in [BuildStatus::Passing, BuildStatus::Failing] {
+            if self.pattern_for_status(status).duration().is_zero() {
+                return Err(format!("{status:?} build pattern must last longer than 0s"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_pattern(&self, name: &str) -> bool {
+        self.patterns.contains_key(name)
+    }
+
+    /// The pattern for the given event kind, falling back to `celebrate`.
+    pub fn pattern_for(&self, kind: EventKind) -> Pattern {
+        self.animations
+            .get(&kind)
+            .and_then(|name| self.patterns.get(name))
+            .cloned()
+            .unwrap_or_else(|| Pattern::parse(CELEBRATE).unwrap())
+    }
+
+    /// The pattern played in a loop, while idle, to show the CI status: the
+    /// `build_passing` or `build_failing` pattern.
+    pub fn pattern_for_status(&self, status: BuildStatus) -> Pattern {
+        let (name, default) = match status {
+            BuildStatus::Passing => ("build_passing", BUILD_PASSING),
+            BuildStatus::Failing => ("build_failing", BUILD_FAILING),
+        };
+        self.patterns
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| Pattern::parse(default).unwrap())
+    }
+
+    pub fn combo_window(&self) -> Duration {
+        Duration::from_millis(self.combo_window_ms)
+    }
+
+    /// The pattern for `count` events of the same kind: the `animation` pattern, or the
+    /// usual one for the kind, for a single event, otherwise the `combo` pattern once per
+    /// event, up to `combo_max` times. Losses don't make a combo, their own pattern is
+    /// repeated instead.
+    pub fn pattern_for_burst(
+        &self,
+        kind: EventKind,
+        animation: Option<&str>,
+        count: usize,
+    ) -> Pattern {
+        if count <= 1 || kind.is_loss() {
+            return animation
+                .and_then(|name| self.patterns.get(name))
+                .cloned()
+                .unwrap_or_else(|| self.pattern_for(kind))
+                .repeated(count.min(self.combo_max).max(1));
+        }
+        self.patterns
+            .get("combo")
+            .cloned()
+            .unwrap_or_else(|| Pattern::parse(COMBO).unwrap())
+            .repeated(count.min(self.combo_max))
+    }
+}
+
+/// Whether it is cancelled, and the condition variable waking up sleeping animations.
+type CancellationState = (Mutex<bool>, Condvar);
+
+/// Stops a running animation. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    state: Arc<CancellationState>,
+    children: Arc<Mutex<Vec<Weak<CancellationState>>>>,
+}
+
+impl Cancellation {
+    pub fn cancel(&self) {
+        let (cancelled, condvar) = &*self.state;
+        *cancelled.lock().unwrap() = true;
+        condvar.notify_all();
+        for child in self.children.lock().unwrap().drain(..) {
+            if let Some(state) = child.upgrade() {
+                *state.0.lock().unwrap() = true;
+                state.1.notify_all();
+            }
+        }
+    }
+
+    /// A cancellation of its own, also cancelled when this one is.
+    pub fn child(&self) -> Cancellation {
+        let child = Cancellation::default();
+        let mut children = self.children.lock().unwrap();
+        children.retain(|child| child.strong_count() > 0);
+        if self.is_cancelled() {
+            *child.state.0.lock().unwrap() = true;
+        } else {
+            children.push(Arc::downgrade(&child.state));
+        }
+        child
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.state.0.lock().unwrap()
+    }
+
+    /// Blocks the current thread for `duration`, waking up early if cancelled.
+    /// Returns `false` if cancelled.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let (cancelled, condvar) = &*self.state;
+        let deadline = Instant::now() + duration;
+        let mut guard = cancelled.lock().unwrap();
+        while !*guard {
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            guard = condvar.wait_timeout(guard, deadline - now).unwrap().0;
+        }
+        false
+    }
+}
+
+/// Plays a pattern. Implementations block the calling thread until the pattern is
+/// finished or `cancellation` is cancelled, so they must not be called from async code
+/// directly.
 pub trait Animation: Send + Sync + Debug {
-    fn animate(&self);
+    fn animate(&self, pattern: &Pattern, cancellation: &Cancellation);
+
+    /// Plays the pattern over and over until `cancellation` is cancelled.
+    fn animate_looped(&self, pattern: &Pattern, cancellation: &Cancellation) {
+        while !cancellation.is_cancelled() {
+            self.animate(pattern, cancellation);
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
-pub struct MockAnimation {}
+/// An output that can show a brightness between `0.0` (off) and `1.0` (full).
+pub trait LedBackend: Send + Sync + Debug {
+    fn set_brightness(&self, brightness: f64);
+}
+
+/// Plays the steps of a pattern against any [`LedBackend`].
+#[derive(Debug)]
+pub struct SequenceAnimation {
+    backend: Arc<dyn LedBackend>,
+}
 
-impl Animation for MockAnimation {
-    fn animate(&self) {
-        info!("MockAnimation animate");
-        thread::sleep(Duration::from_millis(500));
-        info!("MockAnimation finished");
+impl SequenceAnimation {
+    pub fn new(backend: Arc<dyn LedBackend>) -> Self {
+        Self { backend }
+    }
+
+    fn fade(&self, from: f64, to: f64, duration: Duration, cancellation: &Cancellation) -> bool {
+        for step in 0..=FADE_STEPS {
+            let progress = f64::from(step) / f64::from(FADE_STEPS);
+            self.backend.set_brightness(from + (to - from) * progress);
+            if !cancellation.sleep(duration / (FADE_STEPS + 1)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl SequenceAnimation {
+    /// Plays every step, returning `false` if cancelled before the end.
+    fn play(&self, pattern: &Pattern, cancellation: &Cancellation) -> bool {
+        for step in pattern.steps() {
+            trace!("playing {:?}", step);
+            let completed = match *step {
+                Step::On(duration) => {
+                    self.backend.set_brightness(1.0);
+                    cancellation.sleep(duration)
+                }
+                Step::Off(duration) => {
+                    self.backend.set_brightness(0.0);
+                    cancellation.sleep(duration)
+                }
+                Step::FadeIn(duration) => self.fade(0.0, 1.0, duration, cancellation),
+                Step::FadeOut(duration) => self.fade(1.0, 0.0, duration, cancellation),
+            };
+            if !completed {
+                info!("animation cancelled");
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Animation for SequenceAnimation {
+    fn animate(&self, pattern: &Pattern, cancellation: &Cancellation) {
+        self.play(pattern, cancellation);
+        self.backend.set_brightness(0.0);
+    }
+
+    /// The LED is only turned off once cancelled, not between rounds, so a steady pattern
+    /// stays steady.
+    fn animate_looped(&self, pattern: &Pattern, cancellation: &Cancellation) {
+        while !pattern.duration().is_zero() && self.play(pattern, cancellation) {}
+        self.backend.set_brightness(0.0);
+    }
+}
+
+/// Backend that only logs, and remembers, the brightness it is asked to show.
+#[derive(Debug, Default)]
+pub struct MockLed {
+    history: Mutex<Vec<f64>>,
+}
+
+impl MockLed {
+    #[cfg(test)]
+    pub fn history(&self) -> Vec<f64> {
+        self.history.lock().unwrap().clone()
+    }
+}
+
+impl LedBackend for MockLed {
+    fn set_brightness(&self, brightness: f64) {
+        info!("MockLed brightness {:.2}", brightness);
+        self.history.lock().unwrap().push(brightness);
     }
 }
 
 pub fn get_animation(environment: &str) -> Arc<dyn Animation> {
-    match environment {
+    let backend: Arc<dyn LedBackend> = match environment {
         #[cfg(target_arch = "aarch64")]
-        "production" => Arc::new(RaspberryPiAnimation::new()),
-        _ => Arc::new(MockAnimation {}),
+        "production" => Arc::new(RaspberryPiLed::new()),
+        _ => Arc::new(MockLed::default()),
+    };
+    Arc::new(SequenceAnimation::new(backend))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_settings_are_valid() {
+        let settings = AnimationSettings::default();
+        assert!(settings.validate().is_ok());
+        assert_eq!(
+            settings.pattern_for(EventKind::Unstar),
+            Pattern::parse(SAD).unwrap()
+        );
+    }
+
+    #[test]
+    fn settings_from_toml() {
+        let toml = r#"
+        [patterns]
+        quick = "on 100ms, off 200ms, repeat 2"
+
+        [animations]
+        fork = "quick"
+        "#;
+        let settings = crate::settings_from_toml::<AnimationSettings>(toml).unwrap();
+        assert!(settings.validate().is_ok());
+        let quick = settings.pattern_for(EventKind::Fork);
+        assert_eq!(quick.steps().len(), 4);
+        // kinds without a mapping fall back to celebrate
+        assert_eq!(
+            settings.pattern_for(EventKind::Star),
+            Pattern::parse(CELEBRATE).unwrap()
+        );
+    }
+
+    #[test]
+    fn burst_pattern_is_capped() {
+        let toml = r#"
+        combo_max = 3
+
+        [patterns]
+        combo = "on 1ms, off 1ms"
+        "#;
+        let settings = crate::settings_from_toml::<AnimationSettings>(toml).unwrap();
+        assert_eq!(
+            settings.pattern_for_burst(EventKind::Star, None, 1),
+            Pattern::parse(CELEBRATE).unwrap()
+        );
+        assert_eq!(
+            settings.pattern_for_burst(EventKind::Star, None, 2),
+            Pattern::parse("on 1ms, off 1ms, repeat 2").unwrap()
+        );
+        assert_eq!(
+            settings.pattern_for_burst(EventKind::Star, None, 50),
+            Pattern::parse("on 1ms, off 1ms, repeat 3").unwrap()
+        );
+    }
+
+    #[test]
+    fn animation_overrides_the_kind_pattern() {
+        let settings = AnimationSettings::default();
+        assert_eq!(
+            settings.pattern_for_burst(EventKind::Star, Some("sad"), 1),
+            Pattern::parse(SAD).unwrap()
+        );
+        // unknown names fall back to the pattern of the kind
+        assert_eq!(
+            settings.pattern_for_burst(EventKind::Star, Some("missing"), 1),
+            Pattern::parse(CELEBRATE).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_pattern_in_toml_is_reported() {
+        let toml = r#"
+        [patterns]
+        broken = "on 100ms, wiggle 1s"
+        "#;
+        let actual = crate::settings_from_toml::<AnimationSettings>(toml).unwrap_err();
+        assert!(
+            actual
+                .to_string()
+                .contains("pattern 'broken': step 2 'wiggle 1s'")
+        );
+    }
+
+    #[test]
+    fn sequence_animation_drives_backend() {
+        let led = Arc::new(MockLed::default());
+        let animation = SequenceAnimation::new(led.clone());
+        animation.animate(
+            &Pattern::parse("on 1ms, off 1ms, repeat 2").unwrap(),
+            &Cancellation::default(),
+        );
+        assert_eq!(led.history(), vec![1.0, 0.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn sequence_animation_fades() {
+        let led = Arc::new(MockLed::default());
+        let animation = SequenceAnimation::new(led.clone());
+        animation.animate(
+            &Pattern::parse("fade-in 21ms").unwrap(),
+            &Cancellation::default(),
+        );
+        let history = led.history();
+        assert_eq!(history.len(), FADE_STEPS as usize + 2);
+        assert_eq!(history.first(), Some(&0.0));
+        assert_eq!(history[FADE_STEPS as usize], 1.0);
+        assert!(
+            history
+                .windows(2)
+                .take(FADE_STEPS as usize)
+                .all(|w| w[0] < w[1])
+        );
+    }
+
+    #[test]
+    fn cancelled_animation_stops_early() {
+        let led = Arc::new(MockLed::default());
+        let animation = SequenceAnimation::new(led.clone());
+        let cancellation = Cancellation::default();
+        let canceller = cancellation.clone();
+        let handle = std::thread::spawn(move || {
+            animation.animate(&Pattern::parse("on 10s, off 10s").unwrap(), &cancellation)
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        let start = Instant::now();
+        canceller.cancel();
+        handle.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(led.history(), vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn unknown_pattern_is_rejected() {
+        let settings = AnimationSettings {
+            patterns: HashMap::new(),
+            ..AnimationSettings::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn zero_length_build_pattern_is_rejected() {
+        let mut settings = AnimationSettings::default();
+        settings
+            .patterns
+            .insert("build_failing".to_owned(), Pattern::parse("on 0s").unwrap());
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn cancelling_the_parent_cancels_the_child() {
+        let parent = Cancellation::default();
+        let child = parent.child();
+        child.cancel();
+        assert!(!parent.is_cancelled());
+
+        let child = parent.child();
+        parent.cancel();
+        assert!(child.is_cancelled());
+        assert!(parent.child().is_cancelled());
     }
 }
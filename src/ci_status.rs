@@ -0,0 +1,131 @@
+use crate::payloads::{WorkflowConclusion, WorkflowRun};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Overall state of the watched workflows, shown as an ambient animation.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildStatus {
+    Passing,
+    Failing,
+}
+
+/// Which workflow runs drive the CI status light.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct CiSettings {
+    /// Names of the watched workflows, none are watched when empty.
+    workflows: Vec<String>,
+    /// Branches whose runs count, every branch when empty.
+    branches: Vec<String>,
+}
+
+impl CiSettings {
+    fn watches(&self, run: &WorkflowRun) -> bool {
+        self.workflows.iter().any(|w| w == run.name())
+            && (self.branches.is_empty() || self.branches.iter().any(|b| b == run.head_branch()))
+    }
+}
+
+/// Last conclusion of every watched workflow on every branch.
+#[derive(Debug, Default)]
+pub struct CiStatus {
+    settings: CiSettings,
+    // (workflow, branch) -> whether the last completed run failed
+    failures: Mutex<HashMap<(String, String), bool>>,
+}
+
+impl CiStatus {
+    pub fn new(settings: CiSettings) -> Self {
+        Self {
+            settings,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a completed run, returning the overall status if it changed.
+    /// Runs of unwatched workflows, and cancelled or skipped runs, change nothing.
+    pub fn update(&self, run: &WorkflowRun) -> Option<BuildStatus> {
+        if !self.settings.watches(run) {
+            return None;
+        }
+        let failed = match run.conclusion()? {
+            WorkflowConclusion::Success => false,
+            WorkflowConclusion::Failure
+            | WorkflowConclusion::TimedOut
+            | WorkflowConclusion::StartupFailure => true,
+            WorkflowConclusion::Other => return None,
+        };
+        let mut failures = self.failures.lock().unwrap();
+        let before = Self::status(&failures);
+        failures.insert(
+            (run.name().to_owned(), run.head_branch().to_owned()),
+            failed,
+        );
+        let after = Self::status(&failures);
+        if before != after { after } else { None }
+    }
+
+    fn status(failures: &HashMap<(String, String), bool>) -> Option<BuildStatus> {
+        if failures.is_empty() {
+            None
+        } else if failures.values().any(|failed| *failed) {
+            Some(BuildStatus::Failing)
+        } else {
+            Some(BuildStatus::Passing)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str, branch: &str, conclusion: &str) -> WorkflowRun {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "head_branch": branch,
+            "status": "completed",
+            "conclusion": conclusion,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn status_changes_are_reported_once() {
+        let ci = CiStatus::new(CiSettings {
+            workflows: vec!["rust".to_owned()],
+            branches: vec!["main".to_owned(), "release".to_owned()],
+        });
+        assert_eq!(
+            ci.update(&run("rust", "main", "success")),
+            Some(BuildStatus::Passing)
+        );
+        assert_eq!(ci.update(&run("rust", "main", "success")), None);
+        assert_eq!(
+            ci.update(&run("rust", "release", "failure")),
+            Some(BuildStatus::Failing)
+        );
+        // main still passes, but release keeps failing
+        assert_eq!(ci.update(&run("rust", "main", "success")), None);
+        assert_eq!(
+            ci.update(&run("rust", "release", "success")),
+            Some(BuildStatus::Passing)
+        );
+    }
+
+    #[test]
+    fn unwatched_and_inconclusive_runs_are_ignored() {
+        let ci = CiStatus::new(CiSettings {
+            workflows: vec!["rust".to_owned()],
+            branches: vec![],
+        });
+        assert_eq!(ci.update(&run("docs", "main", "failure")), None);
+        assert_eq!(ci.update(&run("rust", "main", "cancelled")), None);
+        assert_eq!(
+            ci.update(&run("rust", "feature", "failure")),
+            Some(BuildStatus::Failing)
+        );
+    }
+}
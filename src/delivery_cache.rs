@@ -0,0 +1,163 @@
+use crate::json_file::BackgroundSaver;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Delivery {
+    id: String,
+    received_at: DateTime<Utc>,
+}
+
+/// Remembers the ids seen recently, at most `capacity` of them and for no longer than `ttl`,
+/// so redelivered webhooks, keyed by `X-GitHub-Delivery`, are only handled once.
+#[derive(Debug)]
+pub struct DeliveryCache {
+    deliveries: Arc<Mutex<VecDeque<Delivery>>>,
+    capacity: usize,
+    ttl: TimeDelta,
+    saver: Option<BackgroundSaver>,
+}
+
+impl DeliveryCache {
+    pub fn new(capacity: usize, ttl: TimeDelta) -> Self {
+        Self {
+            deliveries: Arc::new(Mutex::new(VecDeque::new())),
+            capacity,
+            ttl,
+            saver: None,
+        }
+    }
+
+    /// A cache stored in `path`, loading the deliveries already in it.
+    pub fn persistent(capacity: usize, ttl: TimeDelta, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let deliveries = match fs::read(&path) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                error!("delivery cache {} can't be read: {e}", path.display());
+                VecDeque::new()
+            }),
+            Err(_) => VecDeque::new(),
+        };
+        info!(
+            "{} deliveries loaded from {}",
+            deliveries.len(),
+            path.display()
+        );
+        Self {
+            deliveries: Arc::new(Mutex::new(deliveries)),
+            capacity,
+            ttl,
+            saver: Some(BackgroundSaver::new("delivery cache", path)),
+        }
+    }
+
+    /// Remembers the delivery, returning `false` if it was already seen.
+    pub fn insert(&self, id: &str) -> bool {
+        self.insert_at(id, Utc::now())
+    }
+
+    fn insert_at(&self, id: &str, now: DateTime<Utc>) -> bool {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        while deliveries
+            .front()
+            .is_some_and(|delivery| delivery.received_at + self.ttl < now)
+        {
+            deliveries.pop_front();
+        }
+        if deliveries.iter().any(|delivery| delivery.id == id) {
+            return false;
+        }
+        deliveries.push_back(Delivery {
+            id: id.to_owned(),
+            received_at: now,
+        });
+        while deliveries.len() > self.capacity {
+            deliveries.pop_front();
+        }
+        drop(deliveries);
+        if let Some(saver) = &self.saver {
+            let deliveries = self.deliveries.clone();
+            saver.schedule(move || deliveries.lock().unwrap().clone());
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_is_detected() {
+        let cache = DeliveryCache::new(10, TimeDelta::hours(1));
+        assert!(cache.insert("a"));
+        assert!(cache.insert("b"));
+        assert!(!cache.insert("a"));
+    }
+
+    #[test]
+    fn oldest_is_evicted_when_full() {
+        let cache = DeliveryCache::new(2, TimeDelta::hours(1));
+        assert!(cache.insert("a"));
+        assert!(cache.insert("b"));
+        assert!(cache.insert("c"));
+        assert!(cache.insert("a"));
+        assert!(!cache.insert("c"));
+    }
+
+    #[test]
+    fn expired_delivery_is_forgotten() {
+        let cache = DeliveryCache::new(10, TimeDelta::hours(1));
+        let now = Utc::now();
+        assert!(cache.insert_at("a", now));
+        assert!(!cache.insert_at("a", now + TimeDelta::minutes(59)));
+        assert!(cache.insert_at("a", now + TimeDelta::minutes(61)));
+    }
+
+    #[test]
+    fn deliveries_survive_a_restart() {
+        let path = std::env::temp_dir().join(format!("delivery-cache-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let cache = DeliveryCache::persistent(10, TimeDelta::hours(1), &path);
+        assert!(cache.insert("a"));
+        drop(cache);
+
+        let cache = DeliveryCache::persistent(10, TimeDelta::hours(1), &path);
+        assert!(!cache.insert("a"));
+        assert!(cache.insert("b"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_saved_off_the_runtime() {
+        let path =
+            std::env::temp_dir().join(format!("delivery-cache-{}-async.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let cache = DeliveryCache::persistent(10, TimeDelta::hours(1), &path);
+        assert!(cache.insert("a"));
+        assert!(cache.insert("b"));
+
+        let saved = async {
+            loop {
+                let saved = fs::read(&path)
+                    .ok()
+                    .and_then(|content| serde_json::from_slice::<Vec<Delivery>>(&content).ok());
+                if saved.is_some_and(|saved| saved.len() == 2) {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        };
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_secs(5), saved)
+                .await
+                .is_ok()
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
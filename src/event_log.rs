@@ -0,0 +1,163 @@
+use crate::event_queue::PushOutcome;
+use crate::message_handler::EventKind;
+use crate::payloads::WebhookPayload;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+/// What happened to a received event.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandlingResult {
+    Queued,
+    Coalesced,
+    Dropped,
+    Ignored,
+    Duplicate,
+}
+
+impl From<PushOutcome> for HandlingResult {
+    fn from(outcome: PushOutcome) -> Self {
+        match outcome {
+            // the event is queued, the one it replaced was logged when it arrived
+            PushOutcome::Queued | PushOutcome::Evicted { .. } => HandlingResult::Queued,
+            PushOutcome::Coalesced => HandlingResult::Coalesced,
+            PushOutcome::Dropped => HandlingResult::Dropped,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventRecord {
+    kind: EventKind,
+    repository: Option<String>,
+    sender: String,
+    starred_at: Option<DateTime<Utc>>,
+    delivery_id: Option<String>,
+    received_at: DateTime<Utc>,
+    result: HandlingResult,
+}
+
+impl EventRecord {
+    pub fn new(
+        payload: &impl WebhookPayload,
+        delivery_id: Option<&str>,
+        result: HandlingResult,
+    ) -> Self {
+        Self {
+            kind: payload.kind(),
+            repository: payload
+                .repository()
+                .map(|repository| repository.full_name().to_owned()),
+            sender: payload.sender().login().to_owned(),
+            starred_at: payload.starred_at(),
+            delivery_id: delivery_id.map(str::to_owned),
+            received_at: Utc::now(),
+            result,
+        }
+    }
+}
+
+pub trait EventLog: Send + Sync + Debug {
+    fn record(&self, record: &EventRecord);
+}
+
+/// Keeps nothing, used when no `event_log_path` is configured.
+#[derive(Debug, Clone)]
+pub struct NoEventLog {}
+
+impl EventLog for NoEventLog {
+    fn record(&self, _: &EventRecord) {}
+}
+
+/// Appends every record as one JSON line to a local file.
+#[derive(Debug)]
+pub struct JsonlEventLog {
+    file: Mutex<File>,
+}
+
+impl JsonlEventLog {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl EventLog for JsonlEventLog {
+    fn record(&self, record: &EventRecord) {
+        let mut line = match serde_json::to_vec(record) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("event record can't be serialized: {e}");
+                return;
+            }
+        };
+        line.push(b'\n');
+        // a single write, so a crash can't leave half a line behind another record
+        if let Err(e) = self.file.lock().unwrap().write_all(&line) {
+            error!("event record can't be written: {e}");
+        }
+    }
+}
+
+pub fn get_event_log(path: Option<&str>) -> std::io::Result<Arc<dyn EventLog>> {
+    match path {
+        Some(path) => {
+            info!("recording events in {path}");
+            Ok(Arc::new(JsonlEventLog::open(path)?))
+        }
+        None => Ok(Arc::new(NoEventLog {})),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payloads::StarPayload;
+    use std::fs;
+
+    #[test]
+    fn records_are_appended_as_json_lines() {
+        let path = std::env::temp_dir().join(format!("event-log-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let json = r#"
+        {
+            "action": "created",
+            "starred_at": "2025-06-22T09:56:51Z",
+            "repository": {
+                "id": 123456,
+                "name": "motivate-me",
+                "full_name": "manuelarte/motivate-me"
+            },
+            "sender": {
+                "id": 1,
+                "login": "octocat"
+            }
+        }
+        "#;
+        let payload: StarPayload = serde_json::from_str(json).unwrap();
+
+        let event_log = JsonlEventLog::open(&path).unwrap();
+        let first = EventRecord::new(&payload, Some("delivery-1"), HandlingResult::Queued);
+        let second = EventRecord::new(&payload, None, HandlingResult::Dropped);
+        event_log.record(&first);
+        event_log.record(&second);
+
+        let content = fs::read_to_string(&path).unwrap();
+        let actual: Vec<EventRecord> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(actual, vec![first, second]);
+        assert!(content.contains(r#""repository":"manuelarte/motivate-me""#));
+        assert!(content.contains(r#""starred_at":"2025-06-22T09:56:51Z""#));
+        fs::remove_file(&path).unwrap();
+    }
+}
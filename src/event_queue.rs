@@ -0,0 +1,368 @@
+use crate::message_handler::ActorMessage;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tracing::warn;
+
+/// What to do with a new message when the queue is full.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Drop the message that has been waiting the longest.
+    #[default]
+    DropOldest,
+    /// Drop the new message.
+    DropNewest,
+    /// Merge the new message into a queued one of the same kind and animation, so it is
+    /// still counted in the combo animation. Drops the new message if there is none.
+    Coalesce,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PushOutcome {
+    Queued,
+    Coalesced,
+    /// The new message is lost.
+    Dropped,
+    /// The new message is queued in place of a queued one, lost with the `count` events
+    /// coalesced into it.
+    Evicted {
+        count: usize,
+    },
+}
+
+#[derive(Debug)]
+struct QueuedMessage {
+    message: ActorMessage,
+    count: usize,
+    priority: u8,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    /// Ordered by descending priority, then by arrival.
+    messages: VecDeque<QueuedMessage>,
+    closed: bool,
+}
+
+impl QueueState {
+    fn insert(&mut self, queued: QueuedMessage) {
+        let index = self
+            .messages
+            .iter()
+            .position(|q| q.priority < queued.priority)
+            .unwrap_or(self.messages.len());
+        self.messages.insert(index, queued);
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<QueueState>,
+    notify: Notify,
+    capacity: usize,
+    policy: OverflowPolicy,
+    senders: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+/// Bounded queue of [`ActorMessage`]s whose sender never waits: when the queue is full
+/// the [`OverflowPolicy`] decides which message is lost.
+pub fn channel(capacity: usize, policy: OverflowPolicy) -> (QueueSender, QueueReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(QueueState::default()),
+        notify: Notify::new(),
+        capacity: capacity.max(1),
+        policy,
+        senders: AtomicUsize::new(1),
+        dropped: AtomicU64::new(0),
+    });
+    (
+        QueueSender {
+            shared: shared.clone(),
+        },
+        QueueReceiver { shared },
+    )
+}
+
+#[derive(Debug)]
+pub struct QueueSender {
+    shared: Arc<Shared>,
+}
+
+impl QueueSender {
+    /// Queues `message` behind those of the same or a higher `priority`, so higher
+    /// priorities are played first. When the queue is full, lower priorities are lost first.
+    pub fn push(&self, message: ActorMessage, priority: u8) -> PushOutcome {
+        let outcome = {
+            let mut state = self.shared.state.lock().unwrap();
+            let queued = QueuedMessage {
+                message,
+                count: 1,
+                priority,
+            };
+            if state.messages.len() < self.shared.capacity {
+                state.insert(queued);
+                PushOutcome::Queued
+            } else {
+                match self.shared.policy {
+                    OverflowPolicy::DropOldest => {
+                        // the lowest priority is at the back, its oldest message first
+                        let lowest = state.messages.back().map_or(u8::MAX, |q| q.priority);
+                        let oldest = state.messages.iter().position(|q| q.priority == lowest);
+                        match oldest
+                            .filter(|_| lowest <= priority)
+                            .and_then(|index| state.messages.remove(index))
+                        {
+                            Some(evicted) => {
+                                warn!("queue full, dropping oldest {:?}", evicted);
+                                state.insert(queued);
+                                PushOutcome::Evicted {
+                                    count: evicted.count,
+                                }
+                            }
+                            None => {
+                                warn!("queue full, dropping {:?}", queued.message);
+                                PushOutcome::Dropped
+                            }
+                        }
+                    }
+                    OverflowPolicy::DropNewest => {
+                        if state.messages.back().is_some_and(|q| q.priority < priority) {
+                            let evicted = state.messages.pop_back().unwrap();
+                            warn!("queue full, dropping lower priority {:?}", evicted);
+                            state.insert(queued);
+                            PushOutcome::Evicted {
+                                count: evicted.count,
+                            }
+                        } else {
+                            warn!("queue full, dropping newest {:?}", queued.message);
+                            PushOutcome::Dropped
+                        }
+                    }
+                    OverflowPolicy::Coalesce => {
+                        let same = state
+                            .messages
+                            .iter_mut()
+                            .rev()
+                            .find(|q| q.message == queued.message);
+                        match same {
+                            Some(same) => {
+                                same.count += 1;
+                                PushOutcome::Coalesced
+                            }
+                            None => {
+                                warn!(
+                                    "queue full, nothing to coalesce with, dropping {:?}",
+                                    queued.message
+                                );
+                                PushOutcome::Dropped
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        let lost = match outcome {
+            PushOutcome::Dropped => 1,
+            PushOutcome::Evicted { count } => count as u64,
+            PushOutcome::Queued | PushOutcome::Coalesced => 0,
+        };
+        self.shared.dropped.fetch_add(lost, Ordering::Relaxed);
+        self.shared.notify.notify_one();
+        outcome
+    }
+
+    /// Number of messages lost because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages waiting, counting coalesced ones.
+    pub fn len(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.messages.iter().map(|queued| queued.count).sum()
+    }
+}
+
+impl Clone for QueueSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for QueueSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.state.lock().unwrap().closed = true;
+            self.shared.notify.notify_one();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct QueueReceiver {
+    shared: Arc<Shared>,
+}
+
+impl QueueReceiver {
+    /// Takes the next message, coalesced messages are returned once per event.
+    pub fn try_recv(&mut self) -> Option<ActorMessage> {
+        let mut state = self.shared.state.lock().unwrap();
+        let front = state.messages.front_mut()?;
+        if front.count > 1 {
+            front.count -= 1;
+            return Some(front.message.clone());
+        }
+        state.messages.pop_front().map(|queued| queued.message)
+    }
+
+    /// Whether every sender is dropped, messages may still be waiting.
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().unwrap().closed
+    }
+
+    /// Waits for the next message. Returns `None` once every sender is dropped and the
+    /// queue is empty.
+    pub async fn recv(&mut self) -> Option<ActorMessage> {
+        loop {
+            if let Some(message) = self.try_recv() {
+                return Some(message);
+            }
+            if self.is_closed() {
+                return None;
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_handler::EventKind;
+
+    fn received(kind: EventKind) -> ActorMessage {
+        ActorMessage::MotivationReceived {
+            kind,
+            animation: None,
+        }
+    }
+
+    fn drain(receiver: &mut QueueReceiver) -> Vec<EventKind> {
+        std::iter::from_fn(|| receiver.try_recv())
+            .map(|msg| msg.kind())
+            .collect()
+    }
+
+    #[test]
+    fn drop_oldest_keeps_newest() {
+        let (tx, mut rx) = channel(2, OverflowPolicy::DropOldest);
+        tx.push(received(EventKind::Star), 0);
+        tx.push(received(EventKind::Fork), 0);
+        assert_eq!(
+            tx.push(received(EventKind::Unstar), 0),
+            PushOutcome::Evicted { count: 1 }
+        );
+        assert_eq!(tx.dropped(), 1);
+        assert_eq!(drain(&mut rx), vec![EventKind::Fork, EventKind::Unstar]);
+    }
+
+    #[test]
+    fn drop_newest_keeps_oldest() {
+        let (tx, mut rx) = channel(2, OverflowPolicy::DropNewest);
+        tx.push(received(EventKind::Star), 0);
+        tx.push(received(EventKind::Fork), 0);
+        assert_eq!(
+            tx.push(received(EventKind::Unstar), 0),
+            PushOutcome::Dropped
+        );
+        assert_eq!(tx.dropped(), 1);
+        assert_eq!(drain(&mut rx), vec![EventKind::Star, EventKind::Fork]);
+    }
+
+    #[test]
+    fn coalesce_merges_same_kind() {
+        let (tx, mut rx) = channel(2, OverflowPolicy::Coalesce);
+        tx.push(received(EventKind::Star), 0);
+        tx.push(received(EventKind::Fork), 0);
+        assert_eq!(
+            tx.push(received(EventKind::Star), 0),
+            PushOutcome::Coalesced
+        );
+        assert_eq!(
+            tx.push(received(EventKind::Unstar), 0),
+            PushOutcome::Dropped
+        );
+        assert_eq!(tx.dropped(), 1);
+        assert_eq!(tx.len(), 3);
+        assert_eq!(
+            drain(&mut rx),
+            vec![EventKind::Star, EventKind::Star, EventKind::Fork]
+        );
+    }
+
+    #[tokio::test]
+    async fn recv_ends_when_senders_are_dropped() {
+        let (tx, mut rx) = channel(2, OverflowPolicy::DropOldest);
+        let other = tx.clone();
+        tx.push(received(EventKind::Star), 0);
+        drop(tx);
+        drop(other);
+        assert!(rx.recv().await.is_some());
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn recv_waits_for_a_message() {
+        let (tx, mut rx) = channel(2, OverflowPolicy::DropOldest);
+        let handle = tokio::spawn(async move { rx.recv().await });
+        tokio::task::yield_now().await;
+        tx.push(received(EventKind::Fork), 0);
+        let actual = handle.await.unwrap();
+        assert_eq!(actual.map(|msg| msg.kind()), Some(EventKind::Fork));
+    }
+
+    #[test]
+    fn higher_priority_is_played_first() {
+        let (tx, mut rx) = channel(3, OverflowPolicy::DropOldest);
+        tx.push(received(EventKind::Star), 0);
+        tx.push(received(EventKind::Fork), 0);
+        tx.push(received(EventKind::Release), 5);
+        assert_eq!(
+            drain(&mut rx),
+            vec![EventKind::Release, EventKind::Star, EventKind::Fork]
+        );
+    }
+
+    #[test]
+    fn lower_priority_is_dropped_first() {
+        let (tx, mut rx) = channel(2, OverflowPolicy::DropOldest);
+        tx.push(received(EventKind::Release), 5);
+        tx.push(received(EventKind::Star), 0);
+        assert_eq!(
+            tx.push(received(EventKind::Fork), 1),
+            PushOutcome::Evicted { count: 1 }
+        );
+        assert_eq!(
+            tx.push(received(EventKind::Unstar), 0),
+            PushOutcome::Dropped
+        );
+        assert_eq!(tx.dropped(), 2);
+        assert_eq!(drain(&mut rx), vec![EventKind::Release, EventKind::Fork]);
+
+        let (tx, mut rx) = channel(2, OverflowPolicy::DropNewest);
+        tx.push(received(EventKind::Star), 0);
+        tx.push(received(EventKind::Fork), 0);
+        assert_eq!(
+            tx.push(received(EventKind::Release), 5),
+            PushOutcome::Evicted { count: 1 }
+        );
+        assert_eq!(drain(&mut rx), vec![EventKind::Release, EventKind::Star]);
+    }
+}
@@ -0,0 +1,432 @@
+use chrono::Utc;
+use reqwest::StatusCode;
+use reqwest::header::{ACCEPT, AUTHORIZATION, ETAG, HeaderMap, IF_NONE_MATCH, USER_AGENT};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// Longest wait between two rounds after consecutive failures.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Follows the GitHub events API, for repositories where no webhook can be installed.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct EventsFeedSettings {
+    /// Whose `received_events` are followed, the events of the repositories they watch.
+    user: Option<String>,
+    /// `owner/name` of repositories whose own `events` are followed.
+    repos: Vec<String>,
+    base_url: String,
+    /// Personal access token, only needed for private events or a higher rate limit.
+    token: Option<String>,
+    /// Shortest time between two polls, GitHub may ask for longer with `X-Poll-Interval`.
+    interval_secs: u64,
+}
+
+impl Default for EventsFeedSettings {
+    fn default() -> Self {
+        Self {
+            user: None,
+            repos: vec![],
+            base_url: "https://api.github.com".to_owned(),
+            token: None,
+            interval_secs: 60,
+        }
+    }
+}
+
+impl EventsFeedSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.user.is_some() || !self.repos.is_empty()
+    }
+}
+
+/// A feed entry, or a change the poller noticed, turned into the webhook GitHub would have
+/// sent for it.
+#[derive(Debug, PartialEq)]
+pub struct FeedEvent {
+    /// The `X-GitHub-Event` of the webhook.
+    pub event: &'static str,
+    /// The id of the feed entry, the same in every feed it appears in. Polled changes have
+    /// none.
+    pub delivery_id: Option<String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiRepository {
+    id: i64,
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiEvent {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    actor: Value,
+    repo: ApiRepository,
+    payload: Value,
+}
+
+impl ApiEvent {
+    /// The webhook payload holds the same fields as the entry's `payload`, along with the
+    /// `repository` and the `sender`.
+    fn into_webhook(self) -> Option<FeedEvent> {
+        let event = match self.kind.as_str() {
+            "WatchEvent" => "watch",
+            "ForkEvent" => "fork",
+            "ReleaseEvent" => "release",
+            "PullRequestEvent" => "pull_request",
+            "IssuesEvent" => "issues",
+            "IssueCommentEvent" => "issue_comment",
+            _ => return None,
+        };
+        let Value::Object(mut body) = self.payload else {
+            return None;
+        };
+        let name = self.repo.name.rsplit('/').next().unwrap_or_default();
+        body.insert(
+            "repository".to_owned(),
+            json!({ "id": self.repo.id, "full_name": self.repo.name, "name": name }),
+        );
+        body.insert("sender".to_owned(), self.actor);
+        Some(FeedEvent {
+            event,
+            delivery_id: Some(self.id),
+            body: serde_json::to_vec(&body).ok()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Feed {
+    url: String,
+    etag: Option<String>,
+    /// Id of the newest entry handled, entries up to it are not handled again.
+    last_id: Option<u64>,
+}
+
+/// Outcome of polling one feed.
+#[derive(Debug)]
+struct Polled {
+    events: Vec<FeedEvent>,
+    /// What the response headers ask to wait before polling again.
+    wait: Duration,
+    failed: bool,
+}
+
+#[derive(Debug)]
+pub struct EventsFeed {
+    client: reqwest::Client,
+    settings: EventsFeedSettings,
+    feeds: Vec<Feed>,
+}
+
+impl EventsFeed {
+    pub fn new(settings: EventsFeedSettings) -> Self {
+        let base_url = settings.base_url.trim_end_matches('/');
+        let urls = settings
+            .user
+            .iter()
+            .map(|user| format!("{base_url}/users/{user}/received_events?per_page=100"))
+            .chain(
+                settings
+                    .repos
+                    .iter()
+                    .map(|repo| format!("{base_url}/repos/{repo}/events?per_page=100")),
+            );
+        let feeds = urls
+            .map(|url| Feed {
+                url,
+                etag: None,
+                last_id: None,
+            })
+            .collect();
+        Self {
+            client: reqwest::Client::new(),
+            settings,
+            feeds,
+        }
+    }
+
+    /// Polls every feed forever, passing each new entry to `handle`, oldest first. Rounds
+    /// are spaced as GitHub asks for, twice as long after each failed round.
+    pub async fn run<F>(mut self, handle: F)
+    where
+        F: Fn(FeedEvent),
+    {
+        info!("following {} GitHub events feeds", self.feeds.len());
+        let interval = Duration::from_secs(self.settings.interval_secs);
+        let mut failures = 0;
+        loop {
+            let mut wait = interval;
+            let mut failed = false;
+            for index in 0..self.feeds.len() {
+                let polled = self.poll(index).await;
+                polled.events.into_iter().for_each(&handle);
+                wait = wait.max(polled.wait);
+                failed |= polled.failed;
+            }
+            failures = if failed { failures + 1 } else { 0 };
+            let backoff = interval
+                .saturating_mul(2u32.saturating_pow(failures))
+                .min(MAX_BACKOFF);
+            tokio::time::sleep(wait.max(backoff)).await;
+        }
+    }
+
+    /// Polls the feed at `index` once. The first poll only records the newest entry, so the
+    /// events already in the feed are not celebrated again.
+    async fn poll(&mut self, index: usize) -> Polled {
+        let interval = Duration::from_secs(self.settings.interval_secs);
+        let feed = &self.feeds[index];
+        debug!("GET {}", feed.url);
+        let mut request = self
+            .client
+            .get(&feed.url)
+            .header(ACCEPT, "application/vnd.github+json")
+            .header(USER_AGENT, "motivate-me")
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        if let Some(token) = &self.settings.token {
+            request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+        if let Some(etag) = &feed.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                error!("{} can't be polled: {e}", feed.url);
+                return Polled {
+                    events: vec![],
+                    wait: interval,
+                    failed: true,
+                };
+            }
+        };
+        let wait = wait_from(response.headers(), interval, Utc::now().timestamp());
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return Polled {
+                events: vec![],
+                wait,
+                failed: false,
+            };
+        }
+        if !status.is_success() {
+            warn!("{} answered {status}, retrying in {wait:?}", feed.url);
+            return Polled {
+                events: vec![],
+                wait,
+                failed: true,
+            };
+        }
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+        let entries = match response.json::<Vec<ApiEvent>>().await {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("{} can't be read: {e}", feed.url);
+                return Polled {
+                    events: vec![],
+                    wait,
+                    failed: true,
+                };
+            }
+        };
+
+        let feed = &mut self.feeds[index];
+        feed.etag = etag;
+        let newest = entries
+            .iter()
+            .filter_map(|e| e.id.parse::<u64>().ok())
+            .max();
+        let Some(last_id) = feed.last_id else {
+            feed.last_id = Some(newest.unwrap_or_default());
+            return Polled {
+                events: vec![],
+                wait,
+                failed: false,
+            };
+        };
+        feed.last_id = newest.max(Some(last_id));
+        let events = entries
+            .into_iter()
+            .rev()
+            .filter(|e| e.id.parse::<u64>().is_ok_and(|id| id > last_id))
+            .filter_map(ApiEvent::into_webhook)
+            .collect();
+        Polled {
+            events,
+            wait,
+            failed: false,
+        }
+    }
+}
+
+/// How long to wait before the next poll: at least `interval` and GitHub's `X-Poll-Interval`,
+/// and until the rate limit resets when no request is left.
+fn wait_from(headers: &HeaderMap, interval: Duration, now: i64) -> Duration {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+    let mut wait = interval;
+    if let Some(poll_interval) = header("X-Poll-Interval") {
+        wait = wait.max(Duration::from_secs(poll_interval));
+    }
+    if let Some(retry_after) = header("Retry-After") {
+        wait = wait.max(Duration::from_secs(retry_after));
+    }
+    if header("X-RateLimit-Remaining") == Some(0)
+        && let Some(reset) = header("X-RateLimit-Reset")
+    {
+        wait = wait.max(Duration::from_secs(
+            reset.saturating_sub(now.try_into().unwrap_or_default()),
+        ));
+    }
+    wait
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payloads::{WatchPayload, WebhookPayload};
+    use axum::Router;
+    use axum::extract::State;
+    use axum::http::HeaderValue;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use std::sync::{Arc, Mutex};
+
+    fn entry(id: &str, kind: &str, payload: Value) -> Value {
+        json!({
+            "id": id,
+            "type": kind,
+            "actor": { "id": 1, "login": "octocat" },
+            "repo": { "id": 42, "name": "manuelarte/motivate-me" },
+            "payload": payload,
+            "created_at": "2025-06-22T09:56:51Z",
+        })
+    }
+
+    #[test]
+    fn watch_entry_becomes_a_watch_webhook() {
+        let api_event: ApiEvent =
+            serde_json::from_value(entry("7", "WatchEvent", json!({ "action": "started" })))
+                .unwrap();
+        let event = api_event.into_webhook().unwrap();
+        assert_eq!(event.event, "watch");
+        assert_eq!(event.delivery_id.as_deref(), Some("7"));
+        let payload: WatchPayload = serde_json::from_slice(&event.body).unwrap();
+        assert_eq!(
+            payload.repository().unwrap().full_name(),
+            "manuelarte/motivate-me"
+        );
+        assert_eq!(payload.sender().login(), "octocat");
+
+        let api_event: ApiEvent =
+            serde_json::from_value(entry("8", "PushEvent", json!({ "size": 1 }))).unwrap();
+        assert_eq!(api_event.into_webhook(), None);
+    }
+
+    #[test]
+    fn waits_for_the_poll_interval_and_the_rate_limit() {
+        let interval = Duration::from_secs(60);
+        let mut headers = HeaderMap::new();
+        assert_eq!(wait_from(&headers, interval, 1000), interval);
+
+        headers.insert("X-Poll-Interval", HeaderValue::from_static("90"));
+        headers.insert("X-RateLimit-Remaining", HeaderValue::from_static("12"));
+        headers.insert("X-RateLimit-Reset", HeaderValue::from_static("1600"));
+        assert_eq!(wait_from(&headers, interval, 1000), Duration::from_secs(90));
+
+        headers.insert("X-RateLimit-Remaining", HeaderValue::from_static("0"));
+        assert_eq!(
+            wait_from(&headers, interval, 1000),
+            Duration::from_secs(600)
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct MockFeed {
+        entries: Arc<Mutex<Vec<Value>>>,
+        if_none_match: Arc<Mutex<Vec<Option<String>>>>,
+    }
+
+    async fn events(State(mock): State<MockFeed>, headers: HeaderMap) -> impl IntoResponse {
+        let if_none_match = headers
+            .get(IF_NONE_MATCH)
+            .map(|v| v.to_str().unwrap().to_owned());
+        mock.if_none_match
+            .lock()
+            .unwrap()
+            .push(if_none_match.clone());
+        let entries = mock.entries.lock().unwrap().clone();
+        let etag = format!("\"{}\"", entries.len());
+        if if_none_match.as_deref() == Some(etag.as_str()) {
+            return (StatusCode::NOT_MODIFIED, [(ETAG, etag)], String::new());
+        }
+        (
+            StatusCode::OK,
+            [(ETAG, etag)],
+            Value::from(entries).to_string(),
+        )
+    }
+
+    async fn serve(mock: MockFeed) -> String {
+        let app = Router::new()
+            .route("/repos/manuelarte/motivate-me/events", get(events))
+            .with_state(mock);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{address}")
+    }
+
+    #[tokio::test]
+    async fn new_entries_are_read_once() {
+        let mock = MockFeed::default();
+        *mock.entries.lock().unwrap() =
+            vec![entry("1", "WatchEvent", json!({ "action": "started" }))];
+        let mut feed = EventsFeed::new(EventsFeedSettings {
+            repos: vec!["manuelarte/motivate-me".to_owned()],
+            base_url: serve(mock.clone()).await,
+            ..EventsFeedSettings::default()
+        });
+
+        // already in the feed
+        assert!(feed.poll(0).await.events.is_empty());
+        // not modified
+        assert!(feed.poll(0).await.events.is_empty());
+
+        mock.entries.lock().unwrap().insert(
+            0,
+            entry("3", "ForkEvent", json!({ "forkee": { "id": 43, "name": "motivate-me", "full_name": "octocat/motivate-me", "description": null, "forks_count": 0, "stargazers_count": 0, "private": false } })),
+        );
+        mock.entries
+            .lock()
+            .unwrap()
+            .insert(1, entry("2", "WatchEvent", json!({ "action": "started" })));
+        let polled = feed.poll(0).await;
+        assert!(!polled.failed);
+        let events: Vec<_> = polled
+            .events
+            .iter()
+            .map(|e| (e.event, e.delivery_id.as_deref().unwrap()))
+            .collect();
+        assert_eq!(events, vec![("watch", "2"), ("fork", "3")]);
+
+        assert_eq!(
+            *mock.if_none_match.lock().unwrap(),
+            vec![None, Some("\"1\"".to_owned()), Some("\"1\"".to_owned())]
+        );
+    }
+}
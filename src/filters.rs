@@ -0,0 +1,77 @@
+use crate::message_handler::EventKind;
+use serde::Deserialize;
+
+/// Which `issues` and `issue_comment` events animate.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct IssueFilters {
+    opened: bool,
+    closed: bool,
+    labeled: bool,
+    comment_created: bool,
+    /// `labeled` events only animate for these labels.
+    labels: Vec<String>,
+}
+
+impl Default for IssueFilters {
+    fn default() -> Self {
+        Self {
+            opened: true,
+            closed: false,
+            labeled: true,
+            comment_created: true,
+            labels: vec![],
+        }
+    }
+}
+
+impl IssueFilters {
+    /// Whether an event of this kind, adding `label` if it is a `labeled` action, animates.
+    /// Other event kinds are always allowed.
+    pub fn allows(&self, kind: EventKind, label: Option<&str>) -> bool {
+        match kind {
+            EventKind::IssueOpened => self.opened,
+            EventKind::IssueClosed => self.closed,
+            EventKind::IssueLabeled => {
+                self.labeled
+                    && label.is_some_and(|label| {
+                        self.labels
+                            .iter()
+                            .any(|allowed| allowed.eq_ignore_ascii_case(label))
+                    })
+            }
+            EventKind::IssueComment => self.comment_created,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings_from_toml;
+
+    #[test]
+    fn actions_can_be_turned_off() {
+        let toml = r#"
+        opened = false
+        closed = true
+        "#;
+        let filters = settings_from_toml::<IssueFilters>(toml).unwrap();
+        assert!(!filters.allows(EventKind::IssueOpened, None));
+        assert!(filters.allows(EventKind::IssueClosed, None));
+        assert!(filters.allows(EventKind::IssueComment, None));
+        assert!(filters.allows(EventKind::Star, None));
+    }
+
+    #[test]
+    fn labeled_needs_an_allowed_label() {
+        let filters = IssueFilters {
+            labels: vec!["good first issue".to_owned(), "thank-you".to_owned()],
+            ..IssueFilters::default()
+        };
+        assert!(filters.allows(EventKind::IssueLabeled, Some("Thank-You")));
+        assert!(!filters.allows(EventKind::IssueLabeled, Some("bug")));
+        assert!(!filters.allows(EventKind::IssueLabeled, None));
+    }
+}
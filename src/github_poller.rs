@@ -0,0 +1,710 @@
+use crate::events_feed::FeedEvent;
+use crate::json_file;
+use reqwest::StatusCode;
+use reqwest::header::{ACCEPT, AUTHORIZATION, ETAG, HeaderMap, IF_NONE_MATCH, LINK, USER_AGENT};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{fmt, fs};
+use tracing::{debug, error, info};
+
+const STAR_JSON: &str = "application/vnd.github.star+json";
+const GITHUB_JSON: &str = "application/vnd.github+json";
+const PER_PAGE: u32 = 100;
+
+/// Polls the GitHub REST API instead of waiting for webhooks, for hosts GitHub can't reach.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PollerSettings {
+    /// `owner/name` of the polled repositories, the poller is off when empty.
+    repos: Vec<String>,
+    base_url: String,
+    /// Personal access token, only needed for private repositories or a higher rate limit.
+    token: Option<String>,
+    interval_secs: u64,
+    /// Where the stargazers, forks and releases already seen are kept.
+    state_path: Option<String>,
+}
+
+impl Default for PollerSettings {
+    fn default() -> Self {
+        Self {
+            repos: vec![],
+            base_url: "https://api.github.com".to_owned(),
+            token: None,
+            interval_secs: 300,
+            state_path: None,
+        }
+    }
+}
+
+impl PollerSettings {
+    pub fn is_enabled(&self) -> bool {
+        !self.repos.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.is_enabled() && self.interval_secs == 0 {
+            return Err("interval_secs must be at least 1".to_owned());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiRepository {
+    id: i64,
+    name: String,
+    full_name: String,
+    stargazers_count: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiUser {
+    id: i64,
+    login: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Stargazer {
+    starred_at: Option<String>,
+    user: ApiUser,
+}
+
+#[derive(Deserialize, Debug)]
+struct Fork {
+    full_name: String,
+    owner: Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct Release {
+    id: i64,
+    draft: bool,
+    author: Value,
+}
+
+/// What was seen the last time a repository was polled.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+struct RepoState {
+    /// Id of every stargazer, by login.
+    stargazers: BTreeMap<String, i64>,
+    forks: BTreeSet<String>,
+    /// Only published releases, a draft is celebrated once it is published.
+    releases: BTreeSet<i64>,
+}
+
+/// The stargazers read in one poll.
+#[derive(Debug)]
+struct Stargazers {
+    /// Oldest first.
+    read: Vec<Stargazer>,
+    /// Whether every stargazer was read, or only those after the last one already known.
+    complete: bool,
+}
+
+/// A response kept to ask GitHub whether it changed since, unchanged answers don't count
+/// against the rate limit.
+#[derive(Debug)]
+struct Cached {
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+#[derive(Debug)]
+enum PollError {
+    Request(reqwest::Error),
+    Response(serde_json::Error),
+}
+
+impl fmt::Display for PollError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PollError::Request(e) => write!(f, "{e}"),
+            PollError::Response(e) => write!(f, "unexpected response: {e}"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for PollError {
+    fn from(e: reqwest::Error) -> Self {
+        PollError::Request(e)
+    }
+}
+
+impl From<serde_json::Error> for PollError {
+    fn from(e: serde_json::Error) -> Self {
+        PollError::Response(e)
+    }
+}
+
+#[derive(Debug)]
+pub struct GithubPoller {
+    client: reqwest::Client,
+    settings: PollerSettings,
+    state: HashMap<String, RepoState>,
+    /// Last response of every URL with an `ETag`.
+    cache: HashMap<String, Cached>,
+}
+
+impl GithubPoller {
+    /// Loads the state of the previous polls from `settings.state_path`, if any.
+    pub fn new(settings: PollerSettings) -> Self {
+        let state = match settings
+            .state_path
+            .as_deref()
+            .map(|path| (path, fs::read(path)))
+        {
+            Some((path, Ok(content))) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                error!("poller state {path} can't be read: {e}");
+                HashMap::new()
+            }),
+            _ => HashMap::new(),
+        };
+        Self {
+            client: reqwest::Client::new(),
+            settings,
+            state,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Polls every `interval_secs` forever, passing each change to `handle` as the webhook
+    /// GitHub would have sent for it.
+    pub async fn run<F>(mut self, handle: F)
+    where
+        F: Fn(FeedEvent),
+    {
+        info!("polling {:?}", self.settings.repos);
+        let mut interval = tokio::time::interval(Duration::from_secs(self.settings.interval_secs));
+        loop {
+            interval.tick().await;
+            self.poll().await.into_iter().for_each(&handle);
+        }
+    }
+
+    /// Polls every repository once. The first poll of a repository only records what is
+    /// there, so existing stars are not celebrated again.
+    async fn poll(&mut self) -> Vec<FeedEvent> {
+        let mut events = vec![];
+        for repo in self.settings.repos.clone() {
+            match self.poll_repo(&repo).await {
+                Ok(polled) => events.extend(polled),
+                Err(e) => error!("{repo} can't be polled: {e}"),
+            }
+        }
+        if let Some(path) = &self.settings.state_path {
+            let file = PathBuf::from(path);
+            let state = self.state.clone();
+            let saved = tokio::task::spawn_blocking(move || json_file::save(&file, &state)).await;
+            match saved {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error!("poller state {path} can't be saved: {e}"),
+                Err(e) => error!("poller state can't be saved: {e}"),
+            }
+        }
+        events
+    }
+
+    async fn poll_repo(&mut self, repo: &str) -> Result<Vec<FeedEvent>, PollError> {
+        let base_url = self.settings.base_url.trim_end_matches('/').to_owned();
+        let repository: ApiRepository = self
+            .get(&format!("{base_url}/repos/{repo}"), GITHUB_JSON)
+            .await?
+            .0;
+        let previous = self.state.get(repo).cloned();
+        let stargazers = self
+            .stargazers(
+                &base_url,
+                repo,
+                repository.stargazers_count,
+                previous.as_ref(),
+            )
+            .await?;
+        // newest first, the first page is enough between two polls
+        let forks: Vec<Value> = self
+            .get(
+                &format!("{base_url}/repos/{repo}/forks?sort=newest&per_page={PER_PAGE}"),
+                GITHUB_JSON,
+            )
+            .await?
+            .0;
+        let releases: Vec<Value> = self
+            .get(
+                &format!("{base_url}/repos/{repo}/releases?per_page={PER_PAGE}"),
+                GITHUB_JSON,
+            )
+            .await?
+            .0;
+
+        let Some(previous) = previous else {
+            let state = RepoState {
+                stargazers: stargazers
+                    .read
+                    .iter()
+                    .map(|s| (s.user.login.clone(), s.user.id))
+                    .collect(),
+                forks: forks
+                    .iter()
+                    .map(|fork| Fork::deserialize(fork).map(|fork| fork.full_name))
+                    .collect::<Result<_, _>>()?,
+                releases: published(&releases)?
+                    .into_iter()
+                    .map(|(release, _)| release.id)
+                    .collect(),
+            };
+            info!(
+                "{repo}: {} stargazers, {} forks and {} releases seen",
+                state.stargazers.len(),
+                state.forks.len(),
+                state.releases.len()
+            );
+            self.state.insert(repo.to_owned(), state);
+            return Ok(vec![]);
+        };
+        let mut state = previous.clone();
+        let mut events = vec![];
+
+        let starred: Vec<&Stargazer> = stargazers
+            .read
+            .iter()
+            .filter(|s| !previous.stargazers.contains_key(&s.user.login))
+            .collect();
+        // the stars the repository had when each new one was added, for the milestones
+        let first_count = repository
+            .stargazers_count
+            .saturating_sub(starred.len() as u32);
+        for (stargazer, count) in starred.into_iter().zip(first_count + 1..) {
+            info!("{repo} starred by {}", stargazer.user.login);
+            state
+                .stargazers
+                .insert(stargazer.user.login.clone(), stargazer.user.id);
+            events.push(webhook(
+                "star",
+                json!({
+                    "action": "created",
+                    "starred_at": stargazer.starred_at,
+                    "repository": repository_json(&repository, count),
+                    "sender": { "id": stargazer.user.id, "login": stargazer.user.login },
+                }),
+            )?);
+        }
+        if stargazers.complete {
+            let current: BTreeSet<&str> = stargazers
+                .read
+                .iter()
+                .map(|s| s.user.login.as_str())
+                .collect();
+            for (login, id) in &previous.stargazers {
+                if current.contains(login.as_str()) {
+                    continue;
+                }
+                info!("{repo} unstarred by {login}");
+                state.stargazers.remove(login);
+                events.push(webhook(
+                    "star",
+                    json!({
+                        "action": "deleted",
+                        "starred_at": null,
+                        "repository": repository_json(&repository, repository.stargazers_count),
+                        "sender": { "id": id, "login": login },
+                    }),
+                )?);
+            }
+        }
+
+        for forkee in forks.iter().rev() {
+            let fork = Fork::deserialize(forkee)?;
+            if !state.forks.insert(fork.full_name.clone()) {
+                continue;
+            }
+            info!("{repo} forked as {}", fork.full_name);
+            events.push(webhook(
+                "fork",
+                json!({
+                    "forkee": forkee,
+                    "repository": repository_json(&repository, repository.stargazers_count),
+                    "sender": fork.owner,
+                }),
+            )?);
+        }
+
+        for (release, body) in published(&releases)? {
+            if !state.releases.insert(release.id) {
+                continue;
+            }
+            info!("{repo} released {}", release.id);
+            events.push(webhook(
+                "release",
+                json!({
+                    "action": "published",
+                    "release": body,
+                    "repository": repository_json(&repository, repository.stargazers_count),
+                    "sender": release.author,
+                }),
+            )?);
+        }
+
+        self.state.insert(repo.to_owned(), state);
+        Ok(events)
+    }
+
+    /// Reads the stargazers added since the previous poll. New stars are listed last, so
+    /// only the pages after the last known stargazer are read while nobody unstarred, and
+    /// none while the count is unchanged. A star and an unstar between two polls cancel
+    /// out in the count, they are noticed with the next change.
+    async fn stargazers(
+        &mut self,
+        base_url: &str,
+        repo: &str,
+        count: u32,
+        previous: Option<&RepoState>,
+    ) -> Result<Stargazers, PollError> {
+        let url = |page: u32| {
+            format!("{base_url}/repos/{repo}/stargazers?per_page={PER_PAGE}&page={page}")
+        };
+        let known = previous.map(|state| &state.stargazers);
+        match known {
+            Some(known) if count as usize == known.len() => {
+                return Ok(Stargazers {
+                    read: vec![],
+                    complete: false,
+                });
+            }
+            Some(known) if count as usize > known.len() => {
+                let mut read = vec![];
+                let mut page = count.div_ceil(PER_PAGE);
+                loop {
+                    let mut stargazers: Vec<Stargazer> = self.get(&url(page), STAR_JSON).await?.0;
+                    let reached_known =
+                        stargazers.iter().any(|s| known.contains_key(&s.user.login));
+                    stargazers.append(&mut read);
+                    read = stargazers;
+                    if reached_known || page <= 1 {
+                        break;
+                    }
+                    page -= 1;
+                }
+                let starred = read
+                    .iter()
+                    .filter(|s| !known.contains_key(&s.user.login))
+                    .count();
+                if known.len() + starred == count as usize {
+                    return Ok(Stargazers {
+                        read,
+                        complete: false,
+                    });
+                }
+                // someone unstarred as well, only the whole list tells who
+            }
+            _ => {}
+        }
+        Ok(Stargazers {
+            read: self.get_all(&url(1), STAR_JSON).await?,
+            complete: true,
+        })
+    }
+
+    /// Follows the `next` links.
+    async fn get_all<T>(&mut self, url: &str, accept: &str) -> Result<Vec<T>, PollError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut items = vec![];
+        let mut next = Some(url.to_owned());
+        while let Some(url) = next {
+            let (page, headers) = self.get::<Vec<T>>(&url, accept).await?;
+            items.extend(page);
+            next = next_link(&headers);
+        }
+        Ok(items)
+    }
+
+    /// Asks with the `ETag` of the previous response, if any, and reads it again when
+    /// GitHub answers that nothing changed.
+    async fn get<T>(&mut self, url: &str, accept: &str) -> Result<(T, HeaderMap), PollError>
+    where
+        T: DeserializeOwned,
+    {
+        debug!("GET {url}");
+        let mut request = self
+            .client
+            .get(url)
+            .header(ACCEPT, accept)
+            .header(USER_AGENT, "motivate-me")
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        if let Some(token) = &self.settings.token {
+            request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let cached = self.cache.get(url);
+        if let Some(etag) = cached.and_then(|cached| cached.headers.get(ETAG)) {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let response = request.send().await?;
+        if let Some(cached) = cached.filter(|_| response.status() == StatusCode::NOT_MODIFIED) {
+            return Ok((
+                serde_json::from_slice(&cached.body)?,
+                cached.headers.clone(),
+            ));
+        }
+        let response = response.error_for_status()?;
+        let headers = response.headers().clone();
+        let body = response.bytes().await?.to_vec();
+        let content = serde_json::from_slice(&body)?;
+        if headers.contains_key(ETAG) {
+            self.cache.insert(
+                url.to_owned(),
+                Cached {
+                    headers: headers.clone(),
+                    body,
+                },
+            );
+        }
+        Ok((content, headers))
+    }
+}
+
+/// The releases that are not drafts, along with their JSON, oldest first.
+fn published(releases: &[Value]) -> Result<Vec<(Release, &Value)>, PollError> {
+    let mut published = vec![];
+    for body in releases.iter().rev() {
+        let release = Release::deserialize(body)?;
+        if !release.draft {
+            published.push((release, body));
+        }
+    }
+    Ok(published)
+}
+
+/// The `repository` of a webhook, when it had `stargazers_count` stars.
+fn repository_json(repository: &ApiRepository, stargazers_count: u32) -> Value {
+    json!({
+        "id": repository.id,
+        "name": repository.name,
+        "full_name": repository.full_name,
+        "stargazers_count": stargazers_count,
+    })
+}
+
+fn webhook(event: &'static str, body: Value) -> Result<FeedEvent, PollError> {
+    Ok(FeedEvent {
+        event,
+        delivery_id: None,
+        body: serde_json::to_vec(&body)?,
+    })
+}
+
+/// The `rel="next"` URL of a `Link` header.
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get(LINK)?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (url, rel) = part.split_once(';')?;
+        rel.contains(r#"rel="next""#).then(|| {
+            url.trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_owned()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_handler::EventKind;
+    use crate::payloads::{ReleasePayload, StarPayload, WebhookPayload};
+    use axum::Router;
+    use axum::extract::{Query, State};
+    use axum::http::HeaderValue;
+    use axum::http::header::HOST;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct MockGithub {
+        stargazers: Arc<Mutex<Vec<String>>>,
+        releases: Arc<Mutex<Value>>,
+        /// Pages of stargazers asked for.
+        pages: Arc<Mutex<Vec<u32>>>,
+    }
+
+    #[derive(Deserialize)]
+    struct Page {
+        page: u32,
+    }
+
+    async fn repository(State(mock): State<MockGithub>, headers: HeaderMap) -> impl IntoResponse {
+        let count = mock.stargazers.lock().unwrap().len();
+        let etag = format!("\"{count}\"");
+        if headers
+            .get(IF_NONE_MATCH)
+            .is_some_and(|v| v == etag.as_str())
+        {
+            return (StatusCode::NOT_MODIFIED, [(ETAG, etag)], String::new());
+        }
+        let body = json!({ "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me", "stargazers_count": count });
+        (StatusCode::OK, [(ETAG, etag)], body.to_string())
+    }
+
+    async fn stargazers(
+        State(mock): State<MockGithub>,
+        Query(Page { page }): Query<Page>,
+        headers: HeaderMap,
+    ) -> impl IntoResponse {
+        assert_eq!(headers.get(ACCEPT).unwrap(), STAR_JSON);
+        mock.pages.lock().unwrap().push(page);
+        let stargazers = mock.stargazers.lock().unwrap();
+        let start = (page - 1) as usize * PER_PAGE as usize;
+        let entries: Vec<Value> = stargazers
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(PER_PAGE as usize)
+            .map(|(id, login)| json!({ "starred_at": "2025-06-22T09:56:51Z", "user": { "id": id, "login": login } }))
+            .collect();
+        let link = if start + entries.len() < stargazers.len() {
+            format!(
+                r#"<http://{}/repos/manuelarte/motivate-me/stargazers?per_page={PER_PAGE}&page={}>; rel="next""#,
+                headers.get(HOST).unwrap().to_str().unwrap(),
+                page + 1
+            )
+        } else {
+            String::new()
+        };
+        ([(LINK, link)], Value::from(entries).to_string())
+    }
+
+    async fn releases(State(mock): State<MockGithub>) -> String {
+        mock.releases.lock().unwrap().to_string()
+    }
+
+    /// Serves the mock on a random local port, returning its base URL.
+    async fn serve(mock: MockGithub) -> String {
+        let app = Router::new()
+            .route("/repos/manuelarte/motivate-me", get(repository))
+            .route("/repos/manuelarte/motivate-me/stargazers", get(stargazers))
+            .route(
+                "/repos/manuelarte/motivate-me/forks",
+                get(|| async { "[]" }),
+            )
+            .route("/repos/manuelarte/motivate-me/releases", get(releases))
+            .with_state(mock);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{address}")
+    }
+
+    fn poller(base_url: &str) -> GithubPoller {
+        GithubPoller::new(PollerSettings {
+            repos: vec!["manuelarte/motivate-me".to_owned()],
+            base_url: base_url.to_owned(),
+            ..PollerSettings::default()
+        })
+    }
+
+    fn parse<P: WebhookPayload + DeserializeOwned>(event: &FeedEvent) -> P {
+        serde_json::from_slice(&event.body).unwrap()
+    }
+
+    fn logins(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("user{i}")).collect()
+    }
+
+    #[tokio::test]
+    async fn changes_between_polls_are_sent() {
+        let mock = MockGithub::default();
+        *mock.stargazers.lock().unwrap() = logins(150);
+        *mock.releases.lock().unwrap() = json!([]);
+        let base_url = serve(mock.clone()).await;
+        let mut poller = poller(&base_url);
+
+        assert!(poller.poll().await.is_empty());
+        assert_eq!(*mock.pages.lock().unwrap(), vec![1, 2]);
+
+        mock.stargazers.lock().unwrap().push("monalisa".to_owned());
+        mock.pages.lock().unwrap().clear();
+        let events = poller.poll().await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "star");
+        assert_eq!(events[0].delivery_id, None);
+        let star: StarPayload = parse(&events[0]);
+        assert_eq!(star.kind(), EventKind::Star);
+        assert_eq!(star.sender().login(), "monalisa");
+        assert_eq!(star.repository().unwrap().stargazers_count(), Some(151));
+        // only the last page is read
+        assert_eq!(*mock.pages.lock().unwrap(), vec![2]);
+
+        mock.pages.lock().unwrap().clear();
+        assert!(poller.poll().await.is_empty());
+        // the repository is not modified, no stargazers are read
+        assert!(mock.pages.lock().unwrap().is_empty());
+
+        mock.stargazers
+            .lock()
+            .unwrap()
+            .retain(|login| login != "user3");
+        let events = poller.poll().await;
+        assert_eq!(events.len(), 1);
+        let unstar: StarPayload = parse(&events[0]);
+        assert_eq!(unstar.kind(), EventKind::Unstar);
+        assert_eq!(unstar.sender().login(), "user3");
+
+        assert!(poller.poll().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn draft_is_sent_once_published() {
+        let mock = MockGithub::default();
+        *mock.releases.lock().unwrap() = json!([]);
+        let base_url = serve(mock.clone()).await;
+        let mut poller = poller(&base_url);
+        assert!(poller.poll().await.is_empty());
+
+        let release = |draft| json!([{ "id": 2, "tag_name": "v1.0.0", "name": "v1.0.0", "draft": draft, "prerelease": false, "author": { "id": 1, "login": "manuelarte" } }]);
+        *mock.releases.lock().unwrap() = release(true);
+        assert!(poller.poll().await.is_empty());
+
+        *mock.releases.lock().unwrap() = release(false);
+        let events = poller.poll().await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "release");
+        let published: ReleasePayload = parse(&events[0]);
+        assert_eq!(published.kind(), EventKind::Release);
+        assert!(published.animates());
+        assert_eq!(published.sender().login(), "manuelarte");
+
+        assert!(poller.poll().await.is_empty());
+    }
+
+    #[test]
+    fn zero_interval_is_rejected() {
+        let settings = PollerSettings {
+            repos: vec!["manuelarte/motivate-me".to_owned()],
+            interval_secs: 0,
+            ..PollerSettings::default()
+        };
+        assert!(settings.validate().is_err());
+        assert!(PollerSettings::default().validate().is_ok());
+    }
+
+    #[test]
+    fn next_link_is_found() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            HeaderValue::from_static(
+                r#"<https://api.github.com/repositories/1/stargazers?page=2>; rel="next", <https://api.github.com/repositories/1/stargazers?page=5>; rel="last""#,
+            ),
+        );
+        assert_eq!(
+            next_link(&headers).as_deref(),
+            Some("https://api.github.com/repositories/1/stargazers?page=2")
+        );
+        assert_eq!(next_link(&HeaderMap::new()), None);
+    }
+}
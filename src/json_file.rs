@@ -0,0 +1,83 @@
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+/// Writes `value` as JSON next to `path` and renames it over, so a crash never leaves a
+/// truncated file.
+pub fn save(path: &Path, value: &impl Serialize) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let content = serde_json::to_vec(value).map_err(io::Error::other)?;
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)
+}
+
+/// Saves state shared with the webhook handlers, on a blocking thread when called from the
+/// runtime so webhooks are not answered late because of the disk.
+#[derive(Debug)]
+pub struct BackgroundSaver {
+    /// What is saved, for the logs.
+    name: &'static str,
+    path: PathBuf,
+    /// Whether a save is waiting to run, the changes made meanwhile are saved with it.
+    pending: Arc<AtomicBool>,
+    /// Held while saving, so saves never overlap.
+    saving: Arc<Mutex<()>>,
+}
+
+impl BackgroundSaver {
+    pub fn new(name: &'static str, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name,
+            path: path.into(),
+            pending: Arc::new(AtomicBool::new(false)),
+            saving: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Saves what `snapshot` returns, unless a save is already waiting to run. The snapshot
+    /// is taken when the save runs, so it must read the current state.
+    pub fn schedule<F, T>(&self, snapshot: F)
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Serialize,
+    {
+        if self.pending.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let name = self.name;
+        let path = self.path.clone();
+        let pending = self.pending.clone();
+        let saving = self.saving.clone();
+        let save = move || {
+            let _saving = saving.lock().unwrap();
+            pending.store(false, Ordering::Release);
+            if let Err(e) = save(&path, &snapshot()) {
+                error!("{name} {} can't be saved: {e}", path.display());
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn_blocking(save)),
+            Err(_) => save(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_file_replaces_the_previous_one() {
+        let path = std::env::temp_dir().join(format!("json-file-{}.json", std::process::id()));
+        save(&path, &vec!["a"]).unwrap();
+        save(&path, &vec!["a", "b"]).unwrap();
+        let saved: Vec<String> = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved, vec!["a", "b"]);
+        assert!(!path.with_extension("tmp").exists());
+        fs::remove_file(&path).unwrap();
+    }
+}
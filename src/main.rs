@@ -1,16 +1,46 @@
 mod animation;
+mod ci_status;
+mod delivery_cache;
+mod event_log;
+mod event_queue;
+mod events_feed;
+mod filters;
+mod github_poller;
+mod json_file;
 mod message_handler;
 mod message_listener;
+mod milestones;
+mod pattern;
 mod payloads;
 #[cfg(target_arch = "aarch64")]
 mod raspberrypi_animation;
+mod routing;
 mod signature_validator;
+mod sponsorship;
 
-use crate::animation::get_animation;
-use crate::message_handler::{ActorMessage, MessageHandler};
+use crate::animation::{AnimationSettings, get_animation};
+use crate::ci_status::{CiSettings, CiStatus};
+use crate::delivery_cache::DeliveryCache;
+use crate::event_log::{EventLog, EventRecord, HandlingResult, get_event_log};
+use crate::event_queue::OverflowPolicy;
+use crate::events_feed::{EventsFeed, EventsFeedSettings};
+use crate::filters::IssueFilters;
+use crate::github_poller::{GithubPoller, PollerSettings};
+use crate::message_handler::{EventKind, MessageHandler};
 use crate::message_listener::MessageListener;
-use crate::payloads::{ForkPayload, StarPayload};
-use crate::signature_validator::{SignatureValidator, get_signature_validator};
+use crate::milestones::{MilestoneSettings, Milestones};
+use crate::payloads::{
+    BitbucketForkPayload, BitbucketPullRequestPayload, DiscussionCommentPayload, DiscussionPayload,
+    ForkPayload, GiteaForkPayload, GitlabPushPayload, IssueCommentPayload, IssuesPayload,
+    MotivatePayload, PingPayload, PullRequestPayload, RawAction, ReleasePayload,
+    SponsorshipPayload, StarPayload, WatchPayload, WebhookPayload, WorkflowRunPayload,
+};
+use crate::routing::RoutingRules;
+use crate::signature_validator::{
+    SignatureValidator, get_bitbucket_signature_validator, get_gitea_signature_validator,
+    get_gitlab_token_validator, get_motivate_token_validator, get_signature_validator,
+};
+use crate::sponsorship::SponsorshipTiers;
 use axum::body::Bytes;
 use axum::extract::State;
 use axum::http::HeaderMap;
@@ -20,14 +50,32 @@ use axum::{
     http::StatusCode,
     routing::{get, post},
 };
+use chrono::TimeDelta;
 use config::Config;
 use dotenv::dotenv;
 use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
 use std::io;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::watch;
 use tracing::metadata::LevelFilter;
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
+
+/// GitHub events that trigger an animation, and that a repository hook can subscribe to.
+/// `sponsorship` is handled too, but only sent to the hooks of a sponsors account.
+const HANDLED_EVENTS: &[&str] = &[
+    "star",
+    "fork",
+    "release",
+    "pull_request",
+    "issues",
+    "issue_comment",
+    "discussion",
+    "discussion_comment",
+    "watch",
+    "workflow_run",
+];
 
 #[derive(Debug)]
 pub enum Error {
@@ -40,12 +88,86 @@ struct AppConfig {
     environment: String,
     host: String,
     secret: String,
+    /// Expected `X-Gitlab-Token`, `secret` when not set.
+    gitlab_token: Option<String>,
+    /// Secret of the Gitea and Forgejo webhooks, `secret` when not set.
+    gitea_secret: Option<String>,
+    /// Secret of the Bitbucket webhooks, `secret` when not set.
+    bitbucket_secret: Option<String>,
+    /// Bearer token of `/motivate`, `secret` when not set.
+    motivate_token: Option<String>,
+    #[serde(default)]
+    ignore_unstars: bool,
+    #[serde(default)]
+    skip_team_pull_requests: bool,
+    #[serde(default)]
+    issue_filters: IssueFilters,
+    #[serde(default)]
+    sponsorship_tiers: SponsorshipTiers,
+    /// What to do with the events of each repository, kind, action or sender.
+    #[serde(default)]
+    routes: RoutingRules,
+    #[serde(default)]
+    ci: CiSettings,
+    #[serde(default)]
+    milestones: MilestoneSettings,
+    #[serde(default)]
+    poller: PollerSettings,
+    #[serde(default)]
+    events_feed: EventsFeedSettings,
+    #[serde(default = "default_queue_capacity")]
+    queue_capacity: usize,
+    #[serde(default)]
+    overflow_policy: OverflowPolicy,
+    event_log_path: Option<String>,
+    #[serde(default = "default_delivery_cache_size")]
+    delivery_cache_size: usize,
+    #[serde(default = "default_delivery_cache_ttl_secs")]
+    delivery_cache_ttl_secs: i64,
+    delivery_cache_path: Option<String>,
+    /// A star by the same user on the same repository within this window is only animated
+    /// once, as hooks subscribed to `star` and `watch` receive both events for it.
+    #[serde(default = "default_star_dedup_window_secs")]
+    star_dedup_window_secs: i64,
+    #[serde(flatten, default)]
+    animation: AnimationSettings,
+}
+
+fn default_queue_capacity() -> usize {
+    16
+}
+
+fn default_delivery_cache_size() -> usize {
+    1000
+}
+
+// GitHub lets you redeliver webhooks from the last 3 days
+fn default_delivery_cache_ttl_secs() -> i64 {
+    3 * 24 * 60 * 60
+}
+
+fn default_star_dedup_window_secs() -> i64 {
+    60
 }
 
 #[derive(Debug, Clone)]
 struct AppState {
     signature_validator: Arc<dyn SignatureValidator>,
+    gitlab_token_validator: Arc<dyn SignatureValidator>,
+    gitea_signature_validator: Arc<dyn SignatureValidator>,
+    bitbucket_signature_validator: Arc<dyn SignatureValidator>,
+    motivate_token_validator: Arc<dyn SignatureValidator>,
     actor_handler: Arc<MessageHandler>,
+    event_log: Arc<dyn EventLog>,
+    delivery_cache: Arc<DeliveryCache>,
+    recent_stars: Arc<DeliveryCache>,
+    ignore_unstars: bool,
+    skip_team_pull_requests: bool,
+    issue_filters: IssueFilters,
+    sponsorship_tiers: SponsorshipTiers,
+    routes: RoutingRules,
+    ci_status: Arc<CiStatus>,
+    milestones: Arc<Milestones>,
 }
 
 #[tokio::main]
@@ -59,9 +181,15 @@ async fn main() -> Result<(), Error> {
         // Eg.. `APP_DEBUG=1 ./target/app` would set the `debug` key
         .add_source(config::Environment::with_prefix("MOTIVATE_ME"))
         .build()
-        .unwrap()
-        .try_deserialize::<AppConfig>()
-        .unwrap();
+        .and_then(|config| config.try_deserialize::<AppConfig>());
+    // tracing is configured from the settings, so errors here can only go to stderr
+    let app_config = match app_config {
+        Ok(app_config) => app_config,
+        Err(e) => {
+            eprintln!("invalid configuration: {e}");
+            return Err(Error::GeneralError);
+        }
+    };
 
     let mut level_filter = LevelFilter::INFO;
     if app_config.debug {
@@ -74,35 +202,137 @@ async fn main() -> Result<(), Error> {
         .with_line_number(true)
         .init();
 
-    let (tx, rx) = mpsc::channel::<ActorMessage>(1);
+    if let Err(e) = app_config.animation.validate() {
+        error!("invalid animation settings: {e}");
+        return Err(Error::GeneralError);
+    }
+    if let Err(e) = app_config.poller.validate() {
+        error!("invalid poller settings: {e}");
+        return Err(Error::GeneralError);
+    }
+    if let Some(name) = app_config
+        .sponsorship_tiers
+        .animations()
+        .find(|name| !app_config.animation.has_pattern(name))
+    {
+        error!("sponsorship tier uses unknown animation pattern '{name}'");
+        return Err(Error::GeneralError);
+    }
+    if let Some(name) = app_config
+        .routes
+        .animations()
+        .find(|name| !app_config.animation.has_pattern(name))
+    {
+        error!("route uses unknown animation pattern '{name}'");
+        return Err(Error::GeneralError);
+    }
+
+    let (tx, rx) = event_queue::channel(app_config.queue_capacity, app_config.overflow_policy);
     let animation = get_animation(app_config.environment.as_str());
-    let mut actor = MessageListener::new(rx, animation);
+    let (status_tx, status_rx) = watch::channel(None);
+    let mut actor = MessageListener::new(rx, status_rx, animation, app_config.animation.clone());
 
-    let actor_handler = MessageHandler::new(tx);
+    let actor_handler = Arc::new(MessageHandler::new(tx, status_tx));
+
+    let event_log = get_event_log(app_config.event_log_path.as_deref()).map_err(|e| {
+        error!("event log can't be opened: {e}");
+        Error::GeneralError
+    })?;
+
+    let delivery_cache_ttl = TimeDelta::seconds(app_config.delivery_cache_ttl_secs);
+    let delivery_cache = match &app_config.delivery_cache_path {
+        Some(path) => {
+            DeliveryCache::persistent(app_config.delivery_cache_size, delivery_cache_ttl, path)
+        }
+        None => DeliveryCache::new(app_config.delivery_cache_size, delivery_cache_ttl),
+    };
 
     let signature_validator = get_signature_validator(&app_config);
     let app_state = AppState {
         signature_validator,
-        actor_handler: Arc::new(actor_handler),
+        gitlab_token_validator: get_gitlab_token_validator(&app_config),
+        gitea_signature_validator: get_gitea_signature_validator(&app_config),
+        bitbucket_signature_validator: get_bitbucket_signature_validator(&app_config),
+        motivate_token_validator: get_motivate_token_validator(&app_config),
+        actor_handler: actor_handler.clone(),
+        event_log,
+        delivery_cache: Arc::new(delivery_cache),
+        recent_stars: Arc::new(DeliveryCache::new(
+            app_config.delivery_cache_size,
+            TimeDelta::seconds(app_config.star_dedup_window_secs),
+        )),
+        ignore_unstars: app_config.ignore_unstars,
+        skip_team_pull_requests: app_config.skip_team_pull_requests,
+        issue_filters: app_config.issue_filters.clone(),
+        sponsorship_tiers: app_config.sponsorship_tiers.clone(),
+        routes: app_config.routes.clone(),
+        ci_status: Arc::new(CiStatus::new(app_config.ci.clone())),
+        milestones: Arc::new(Milestones::new(app_config.milestones.clone())),
     };
 
-    let app = Router::new()
-        .route("/", get(root))
-        .route("/github_webhook", post(github_webhook))
-        .with_state(app_state.clone());
+    if app_config.events_feed.is_enabled() {
+        let feed = EventsFeed::new(app_config.events_feed.clone());
+        let state = app_state.clone();
+        tokio::spawn(async move {
+            feed.run(|event| {
+                handle_github_event(
+                    &state,
+                    event.event,
+                    &event.body,
+                    event.delivery_id.as_deref(),
+                );
+            })
+            .await
+        });
+    }
+
+    if app_config.poller.is_enabled() {
+        let poller = GithubPoller::new(app_config.poller.clone());
+        let state = app_state.clone();
+        tokio::spawn(async move {
+            poller
+                .run(|event| {
+                    handle_github_event(&state, event.event, &event.body, None);
+                })
+                .await
+        });
+    }
+
+    let app = app(app_state);
 
+    let cancellation = actor.cancellation();
     let backend = async move {
         info!("{}: {}", "Starting web server in", app_config.host);
         let listener = tokio::net::TcpListener::bind(app_config.host)
             .await
             .unwrap();
-        axum::serve(listener, app).await
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                tokio::signal::ctrl_c().await.ok();
+                info!("shutting down");
+                cancellation.cancel();
+            })
+            .await
     };
     tokio::spawn(async move { actor.run().await });
 
-    tokio::join!(backend);
+    let (result,) = tokio::join!(backend);
+    result.map_err(|e| {
+        error!("web server stopped: {e}");
+        Error::GeneralError
+    })
+}
 
-    Ok(())
+fn app(app_state: AppState) -> Router {
+    Router::new()
+        .route("/", get(root))
+        .route("/metrics", get(metrics))
+        .route("/github_webhook", post(github_webhook))
+        .route("/gitlab_webhook", post(gitlab_webhook))
+        .route("/gitea_webhook", post(gitea_webhook))
+        .route("/bitbucket_webhook", post(bitbucket_webhook))
+        .route("/motivate", post(motivate))
+        .with_state(app_state)
 }
 
 // basic handler that responds with a static string
@@ -110,6 +340,18 @@ async fn root() -> &'static str {
     "Hello, World!"
 }
 
+// Prometheus text format
+async fn metrics(State(state): State<AppState>) -> String {
+    format!(
+        "# TYPE motivate_me_dropped_events_total counter\n\
+         motivate_me_dropped_events_total {}\n\
+         # TYPE motivate_me_queued_events gauge\n\
+         motivate_me_queued_events {}\n",
+        state.actor_handler.dropped_events(),
+        state.actor_handler.queued_events(),
+    )
+}
+
 #[instrument]
 async fn github_webhook(
     State(state): State<AppState>,
@@ -120,56 +362,1273 @@ async fn github_webhook(
     let signature = headers
         .get("X-Hub-Signature-256")
         .and_then(|v| v.to_str().ok());
-    match signature {
-        Some(signature) => {
-            state
-                .clone()
-                .signature_validator
-                .validate(body.iter().as_slice(), signature);
-            let event = headers.get("X-GitHub-Event").and_then(|v| v.to_str().ok());
-            // add logs
-            match event {
-                Some("star") => match serde_json::from_slice::<StarPayload>(&body) {
-                    Ok(payload) => {
-                        debug!("star event processed");
-                        state.actor_handler.motivation_received().await;
-                        (StatusCode::OK, format!("Star event: {payload:?}"))
-                    }
-                    Err(e) => {
-                        error!("{}: {}", "star event can't be processed", e.to_string());
-                        (
-                            StatusCode::BAD_REQUEST,
-                            format!("Invalid star payload: {e}"),
-                        )
-                    }
-                },
-                Some("fork") => match serde_json::from_slice::<ForkPayload>(&body) {
-                    Ok(payload) => {
-                        debug!("fork event processed");
-                        state.actor_handler.motivation_received().await;
-                        (StatusCode::OK, format!("Fork event: {payload:?}"))
-                    }
-                    Err(e) => {
-                        error!("{}: {}", "fork event can't be processed", e.to_string());
-                        (
-                            StatusCode::BAD_REQUEST,
-                            format!("Invalid fork payload: {e}"),
-                        )
-                    }
-                },
-                Some(other) => (
-                    StatusCode::BAD_REQUEST,
-                    format!("Unsupported event type: {other}"),
-                ),
-                None => (
+    let Some(signature) = signature else {
+        error!("no signature found");
+        return (StatusCode::UNAUTHORIZED, "no signature found".to_owned());
+    };
+    if !state.signature_validator.validate(&body, signature) {
+        error!("invalid signature");
+        return (StatusCode::UNAUTHORIZED, "invalid signature".to_owned());
+    }
+
+    let event = headers.get("X-GitHub-Event").and_then(|v| v.to_str().ok());
+    let delivery_id = headers
+        .get("X-GitHub-Delivery")
+        .and_then(|v| v.to_str().ok());
+    match event {
+        Some("ping") => match serde_json::from_slice::<PingPayload>(&body) {
+            Ok(payload) => {
+                info!("ping received, hook subscribed to: {:?}", payload.events());
+                let missing = payload.missing_events(HANDLED_EVENTS);
+                if !missing.is_empty() {
+                    warn!("hook is not subscribed to handled events: {:?}", missing);
+                }
+                (StatusCode::OK, format!("Ping event: {payload:?}"))
+            }
+            Err(e) => {
+                error!("{}: {}", "ping event can't be processed", e.to_string());
+                (
                     StatusCode::BAD_REQUEST,
-                    "Missing X-GitHub-Event header".to_owned(),
-                ),
+                    format!("Invalid ping payload: {e}"),
+                )
+            }
+        },
+        Some(other) => handle_github_event(&state, other, &body, delivery_id),
+        None => (
+            StatusCode::BAD_REQUEST,
+            "Missing X-GitHub-Event header".to_owned(),
+        ),
+    }
+}
+
+#[instrument(skip(state, headers, body))]
+async fn gitlab_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    info!("new gitlab webhook received");
+    let token = headers.get("X-Gitlab-Token").and_then(|v| v.to_str().ok());
+    let Some(token) = token else {
+        error!("no token found");
+        return (StatusCode::UNAUTHORIZED, "no token found".to_owned());
+    };
+    if !state.gitlab_token_validator.validate(&body, token) {
+        error!("invalid token");
+        return (StatusCode::UNAUTHORIZED, "invalid token".to_owned());
+    }
+
+    let event = headers.get("X-Gitlab-Event").and_then(|v| v.to_str().ok());
+    // the same for every retry of a webhook
+    let delivery_id = headers.get("Idempotency-Key").and_then(|v| v.to_str().ok());
+    match event {
+        Some("Push Hook") => {
+            handle_payload::<GitlabPushPayload>(&state, "gitlab push", &body, delivery_id)
+        }
+        Some(other) => (
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported event type: {other}"),
+        ),
+        None => (
+            StatusCode::BAD_REQUEST,
+            "Missing X-Gitlab-Event header".to_owned(),
+        ),
+    }
+}
+
+/// Gitea and Forgejo send GitHub-like payloads, Forgejo with its own header names too.
+#[instrument(skip(state, headers, body))]
+async fn gitea_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    info!("new gitea webhook received");
+    let header = |names: [&str; 2]| {
+        names
+            .iter()
+            .find_map(|name| headers.get(*name).and_then(|v| v.to_str().ok()))
+    };
+    let Some(signature) = header(["X-Forgejo-Signature", "X-Gitea-Signature"]) else {
+        error!("no signature found");
+        return (StatusCode::UNAUTHORIZED, "no signature found".to_owned());
+    };
+    if !state.gitea_signature_validator.validate(&body, signature) {
+        error!("invalid signature");
+        return (StatusCode::UNAUTHORIZED, "invalid signature".to_owned());
+    }
+
+    let event = header(["X-Forgejo-Event", "X-Gitea-Event"]);
+    let delivery_id = header(["X-Forgejo-Delivery", "X-Gitea-Delivery"]);
+    match event {
+        Some("star") => handle_payload::<StarPayload>(&state, "star", &body, delivery_id),
+        Some("fork") => handle_payload::<GiteaForkPayload>(&state, "fork", &body, delivery_id),
+        Some("release") => handle_payload::<ReleasePayload>(&state, "release", &body, delivery_id),
+        Some(other) => (
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported event type: {other}"),
+        ),
+        None => (
+            StatusCode::BAD_REQUEST,
+            "Missing X-Gitea-Event header".to_owned(),
+        ),
+    }
+}
+
+#[instrument(skip(state, headers, body))]
+async fn bitbucket_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    info!("new bitbucket webhook received");
+    let signature = headers.get("X-Hub-Signature").and_then(|v| v.to_str().ok());
+    let Some(signature) = signature else {
+        error!("no signature found");
+        return (StatusCode::UNAUTHORIZED, "no signature found".to_owned());
+    };
+    if !state
+        .bitbucket_signature_validator
+        .validate(&body, signature)
+    {
+        error!("invalid signature");
+        return (StatusCode::UNAUTHORIZED, "invalid signature".to_owned());
+    }
+
+    let event = headers.get("X-Event-Key").and_then(|v| v.to_str().ok());
+    let delivery_id = headers.get("X-Request-UUID").and_then(|v| v.to_str().ok());
+    match event {
+        Some("repo:fork") => {
+            handle_payload::<BitbucketForkPayload>(&state, "bitbucket fork", &body, delivery_id)
+        }
+        Some("pullrequest:fulfilled") => handle_payload::<BitbucketPullRequestPayload>(
+            &state,
+            "bitbucket pull request",
+            &body,
+            delivery_id,
+        ),
+        Some(other) => (
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported event type: {other}"),
+        ),
+        None => (
+            StatusCode::BAD_REQUEST,
+            "Missing X-Event-Key header".to_owned(),
+        ),
+    }
+}
+
+/// Motivation from anything that is not a forge, authenticated with a bearer token or,
+/// like GitHub's, with an HMAC signature of the body in `X-Hub-Signature-256`.
+#[instrument(skip(state, headers, body))]
+async fn motivate(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    info!("new motivation received");
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let token = header("Authorization").and_then(|v| v.strip_prefix("Bearer "));
+    let authenticated = match (token, header("X-Hub-Signature-256")) {
+        (Some(token), _) => state.motivate_token_validator.validate(&body, token),
+        (None, Some(signature)) => state.signature_validator.validate(&body, signature),
+        (None, None) => {
+            error!("no token or signature found");
+            return (
+                StatusCode::UNAUTHORIZED,
+                "no token or signature found".to_owned(),
+            );
+        }
+    };
+    if !authenticated {
+        error!("invalid token or signature");
+        return (
+            StatusCode::UNAUTHORIZED,
+            "invalid token or signature".to_owned(),
+        );
+    }
+    handle_payload::<MotivatePayload>(&state, "motivate", &body, header("Idempotency-Key"))
+}
+
+/// Handles a GitHub event, received by `github_webhook` or read from the events feed.
+fn handle_github_event(
+    state: &AppState,
+    event: &str,
+    body: &[u8],
+    delivery_id: Option<&str>,
+) -> (StatusCode, String) {
+    match event {
+        "star" => handle_payload::<StarPayload>(state, "star", body, delivery_id),
+        "fork" => handle_payload::<ForkPayload>(state, "fork", body, delivery_id),
+        "release" => handle_payload::<ReleasePayload>(state, "release", body, delivery_id),
+        "pull_request" => {
+            handle_payload::<PullRequestPayload>(state, "pull request", body, delivery_id)
+        }
+        "issues" => handle_payload::<IssuesPayload>(state, "issues", body, delivery_id),
+        "issue_comment" => {
+            handle_payload::<IssueCommentPayload>(state, "issue comment", body, delivery_id)
+        }
+        "discussion" => handle_payload::<DiscussionPayload>(state, "discussion", body, delivery_id),
+        "discussion_comment" => handle_payload::<DiscussionCommentPayload>(
+            state,
+            "discussion comment",
+            body,
+            delivery_id,
+        ),
+        "workflow_run" => {
+            handle_payload::<WorkflowRunPayload>(state, "workflow run", body, delivery_id)
+        }
+        "watch" => handle_payload::<WatchPayload>(state, "watch", body, delivery_id),
+        "sponsorship" => {
+            handle_payload::<SponsorshipPayload>(state, "sponsorship", body, delivery_id)
+        }
+        other => (
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported event type: {other}"),
+        ),
+    }
+}
+
+/// Parses a payload of the given event and hands it to [`handle_motivation`].
+fn handle_payload<P>(
+    state: &AppState,
+    event: &str,
+    body: &[u8],
+    delivery_id: Option<&str>,
+) -> (StatusCode, String)
+where
+    P: WebhookPayload + DeserializeOwned + Debug,
+{
+    match serde_json::from_slice::<P>(body) {
+        Ok(payload) => {
+            debug!("{event} event processed");
+            let raw_action = serde_json::from_slice::<RawAction>(body).ok();
+            let action = raw_action.as_ref().and_then(RawAction::action);
+            let result = handle_motivation(state, &payload, action, delivery_id);
+            respond(result, format!("{event} event: {payload:?}"))
+        }
+        Err(e) => {
+            error!("{event} event can't be processed: {e}");
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid {event} payload: {e}"),
+            )
+        }
+    }
+}
+
+/// Sends the event to the animation queue, unless it is ignored or was already delivered,
+/// and records the outcome. `action` is the webhook's own, which routes match on.
+fn handle_motivation(
+    state: &AppState,
+    payload: &impl WebhookPayload,
+    action: Option<&str>,
+    delivery_id: Option<&str>,
+) -> HandlingResult {
+    let duplicate = delivery_id.is_some_and(|id| !state.delivery_cache.insert(id));
+    let route = state.routes.route(
+        payload.repository().map(|r| r.full_name()),
+        payload.kind(),
+        action,
+        payload.sender().login(),
+    );
+    let priority = route.map_or(0, |route| route.priority());
+    let result = match payload.kind() {
+        _ if duplicate => {
+            info!("delivery {:?} already handled", delivery_id);
+            HandlingResult::Duplicate
+        }
+        EventKind::Star if !state.recent_stars.insert(&star_key(payload)) => {
+            info!("star by {} already handled", payload.sender().login());
+            HandlingResult::Duplicate
+        }
+        kind if route.is_some_and(|route| route.drops()) => {
+            debug!("{:?} event dropped by a route", kind);
+            HandlingResult::Ignored
+        }
+        kind if !payload.animates() => {
+            debug!("{:?} event does not animate", kind);
+            HandlingResult::Ignored
+        }
+        EventKind::WorkflowRun => match payload
+            .workflow_run()
+            .and_then(|run| state.ci_status.update(run))
+        {
+            Some(status) => state.actor_handler.build_status_changed(status).into(),
+            None => {
+                debug!("CI status unchanged");
+                HandlingResult::Ignored
+            }
+        },
+        kind if !state.issue_filters.allows(kind, payload.label()) => {
+            debug!("{:?} event filtered out", kind);
+            HandlingResult::Ignored
+        }
+        EventKind::PullRequestMerged
+            if state.skip_team_pull_requests
+                && payload.author_association().is_some_and(|a| a.is_team()) =>
+        {
+            debug!("pull request from the team ignored");
+            HandlingResult::Ignored
+        }
+        EventKind::Unstar if state.ignore_unstars => {
+            debug!("unstar ignored");
+            HandlingResult::Ignored
+        }
+        EventKind::Star
+            if payload
+                .repository()
+                .and_then(|repository| state.milestones.crossed(repository))
+                .is_some() =>
+        {
+            state
+                .actor_handler
+                .motivation_received(EventKind::StarMilestone, None, priority)
+                .into()
+        }
+        kind @ (EventKind::Unstar | EventKind::SponsorshipCancelled) => {
+            state.actor_handler.motivation_lost(kind, priority).into()
+        }
+        kind => {
+            let animation = route
+                .and_then(|route| route.animation())
+                .or_else(|| payload.animation())
+                .or_else(|| {
+                    payload
+                        .monthly_price_in_dollars()
+                        .and_then(|dollars| state.sponsorship_tiers.animation_for(dollars))
+                });
+            state
+                .actor_handler
+                .motivation_received(kind, animation, priority)
+                .into()
+        }
+    };
+    state
+        .event_log
+        .record(&EventRecord::new(payload, delivery_id, result));
+    result
+}
+
+/// Identifies a star regardless of whether it arrived as a `star` or a `watch` event.
+fn star_key(payload: &impl WebhookPayload) -> String {
+    let repository = payload.repository().map_or("", |r| r.full_name());
+    format!("{repository}/{}", payload.sender().login())
+}
+
+fn respond(result: HandlingResult, description: String) -> (StatusCode, String) {
+    match result {
+        HandlingResult::Duplicate => (StatusCode::OK, "duplicate".to_owned()),
+        HandlingResult::Ignored => (StatusCode::OK, description),
+        _ => (StatusCode::ACCEPTED, description),
+    }
+}
+
+/// Reads settings from a TOML string, the way `Settings.toml` is read.
+#[cfg(test)]
+fn settings_from_toml<T: serde::de::DeserializeOwned>(
+    toml: &str,
+) -> Result<T, config::ConfigError> {
+    Config::builder()
+        .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+        .build()?
+        .try_deserialize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ci_status::BuildStatus;
+    use crate::event_log::NoEventLog;
+    use crate::event_queue::QueueReceiver;
+    use crate::message_handler::ActorMessage;
+    use crate::signature_validator::{
+        GiteaSignatureValidator, Rsa256SignatureValidator, TokenValidator,
+    };
+    use axum::body::Body;
+    use axum::http::Request;
+    use hmac::{Hmac, KeyInit, Mac};
+    use sha2::Sha256;
+    use std::sync::Mutex;
+    use tower::ServiceExt;
+
+    const SECRET: &str = "It's a Secret to Everybody";
+    const STAR_PAYLOAD: &str = r#"
+    {
+        "action": "created",
+        "repository": {
+            "id": 123456,
+            "name": "motivate-me",
+            "full_name": "manuelarte/motivate-me"
+        },
+        "sender": {
+            "id": 1,
+            "login": "octocat"
+        }
+    }
+    "#;
+
+    fn test_state() -> (AppState, QueueReceiver) {
+        let (tx, rx) = event_queue::channel(1, OverflowPolicy::DropNewest);
+        let app_state = AppState {
+            signature_validator: Arc::new(Rsa256SignatureValidator::new(SECRET)),
+            gitlab_token_validator: Arc::new(TokenValidator::new(SECRET)),
+            gitea_signature_validator: Arc::new(GiteaSignatureValidator::new(SECRET)),
+            bitbucket_signature_validator: Arc::new(Rsa256SignatureValidator::new(SECRET)),
+            motivate_token_validator: Arc::new(TokenValidator::new(MOTIVATE_TOKEN)),
+            actor_handler: Arc::new(MessageHandler::new(tx, watch::channel(None).0)),
+            event_log: Arc::new(NoEventLog {}),
+            delivery_cache: Arc::new(DeliveryCache::new(10, TimeDelta::hours(1))),
+            recent_stars: Arc::new(DeliveryCache::new(10, TimeDelta::minutes(1))),
+            ignore_unstars: false,
+            skip_team_pull_requests: false,
+            issue_filters: IssueFilters::default(),
+            sponsorship_tiers: SponsorshipTiers::default(),
+            routes: RoutingRules::default(),
+            ci_status: Arc::new(CiStatus::default()),
+            milestones: Arc::new(Milestones::new(MilestoneSettings::default())),
+        };
+        (app_state, rx)
+    }
+
+    fn test_app() -> (Router, QueueReceiver) {
+        let (app_state, rx) = test_state();
+        (app(app_state), rx)
+    }
+
+    #[derive(Debug, Default)]
+    struct MemoryEventLog {
+        records: Mutex<Vec<EventRecord>>,
+    }
+
+    impl EventLog for MemoryEventLog {
+        fn record(&self, record: &EventRecord) {
+            self.records.lock().unwrap().push(record.clone());
+        }
+    }
+
+    fn signed_request(event: &str, payload: &str) -> Request<Body> {
+        Request::post("/github_webhook")
+            .header("X-GitHub-Event", event)
+            .header("X-Hub-Signature-256", sign(payload))
+            .body(Body::from(payload.to_owned()))
+            .unwrap()
+    }
+
+    fn sign(payload: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(payload.as_bytes());
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn star_request(signature: Option<&str>) -> Request<Body> {
+        let mut builder = Request::post("/github_webhook")
+            .header("X-GitHub-Event", "star")
+            .header("Content-Type", "application/json");
+        if let Some(signature) = signature {
+            builder = builder.header("X-Hub-Signature-256", signature);
+        }
+        builder.body(Body::from(STAR_PAYLOAD)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn ping_is_answered_without_animation() {
+        let (app, mut rx) = test_app();
+        let payload = r#"
+        {
+          "zen": "Keep it logically awesome.",
+          "hook_id": 42,
+          "hook": { "id": 42, "active": true, "events": ["star"], "config": {} }
+        }
+        "#;
+        let response = app.oneshot(signed_request("ping", payload)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(rx.try_recv().is_none());
+    }
+
+    const UNSTAR_PAYLOAD: &str = r#"
+    {
+        "action": "deleted",
+        "repository": {
+            "id": 123456,
+            "name": "motivate-me",
+            "full_name": "manuelarte/motivate-me"
+        },
+        "sender": {
+            "id": 1,
+            "login": "octocat"
+        }
+    }
+    "#;
+
+    #[tokio::test]
+    async fn unstar_sends_motivation_lost() {
+        let (app, mut rx) = test_app();
+        let response = app
+            .oneshot(signed_request("star", UNSTAR_PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(matches!(
+            rx.try_recv(),
+            Some(ActorMessage::MotivationLost {
+                kind: EventKind::Unstar
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_recorded() {
+        let event_log = Arc::new(MemoryEventLog::default());
+        let (mut app_state, _rx) = test_state();
+        app_state.ignore_unstars = true;
+        app_state.event_log = event_log.clone();
+        let app = app(app_state);
+        let mut request = signed_request("star", UNSTAR_PAYLOAD);
+        request
+            .headers_mut()
+            .insert("X-GitHub-Delivery", "delivery-1".parse().unwrap());
+        app.oneshot(request).await.unwrap();
+
+        let records = event_log.records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        let record = serde_json::to_value(&records[0]).unwrap();
+        assert_eq!(record["kind"], "unstar");
+        assert_eq!(record["repository"], "manuelarte/motivate-me");
+        assert_eq!(record["sender"], "octocat");
+        assert_eq!(record["delivery_id"], "delivery-1");
+        assert_eq!(record["result"], "ignored");
+    }
+
+    #[tokio::test]
+    async fn redelivery_is_not_animated_twice() {
+        let (app, mut rx) = test_app();
+        for expected in [StatusCode::ACCEPTED, StatusCode::OK] {
+            let mut request = signed_request("star", STAR_PAYLOAD);
+            request
+                .headers_mut()
+                .insert("X-GitHub-Delivery", "delivery-1".parse().unwrap());
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), expected);
+        }
+        assert!(rx.try_recv().is_some());
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn published_prerelease_is_animated() {
+        let (app, mut rx) = test_app();
+        let payload = r#"
+        {
+          "action": "published",
+          "release": {
+            "tag_name": "v0.2.0-rc.1",
+            "name": "v0.2.0-rc.1",
+            "draft": false,
+            "prerelease": true,
+            "author": { "id": 1, "login": "octocat" }
+          },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 1, "login": "octocat" }
+        }
+        "#;
+        let response = app
+            .oneshot(signed_request("release", payload))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(matches!(
+            rx.try_recv(),
+            Some(ActorMessage::MotivationReceived {
+                kind: EventKind::Prerelease,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn edited_release_is_not_animated() {
+        let (app, mut rx) = test_app();
+        let payload = r#"
+        {
+          "action": "edited",
+          "release": {
+            "tag_name": "v0.1.0",
+            "name": "v0.1.0",
+            "draft": false,
+            "prerelease": false,
+            "author": { "id": 1, "login": "octocat" }
+          },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 1, "login": "octocat" }
+        }
+        "#;
+        let response = app
+            .oneshot(signed_request("release", payload))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(rx.try_recv().is_none());
+    }
+
+    const MERGED_PULL_REQUEST_PAYLOAD: &str = r#"
+    {
+      "action": "closed",
+      "pull_request": {
+        "number": 7,
+        "title": "Fix typo",
+        "merged": true,
+        "author_association": "MEMBER",
+        "user": { "id": 2, "login": "teammate" },
+        "base": { "repo": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" } }
+      },
+      "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+      "sender": { "id": 2, "login": "teammate" }
+    }
+    "#;
+
+    #[tokio::test]
+    async fn merged_pull_request_is_animated() {
+        let (app, mut rx) = test_app();
+        let response = app
+            .oneshot(signed_request("pull_request", MERGED_PULL_REQUEST_PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(matches!(
+            rx.try_recv(),
+            Some(ActorMessage::MotivationReceived {
+                kind: EventKind::PullRequestMerged,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn team_pull_request_can_be_skipped() {
+        let (mut app_state, mut rx) = test_state();
+        app_state.skip_team_pull_requests = true;
+        let response = app(app_state)
+            .oneshot(signed_request("pull_request", MERGED_PULL_REQUEST_PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(rx.try_recv().is_none());
+    }
+
+    const LABELED_ISSUE_PAYLOAD: &str = r#"
+    {
+      "action": "labeled",
+      "issue": {
+        "number": 3,
+        "title": "Add a buzzer",
+        "user": { "id": 2, "login": "contributor" },
+        "author_association": "NONE"
+      },
+      "label": { "name": "thank-you" },
+      "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+      "sender": { "id": 2, "login": "contributor" }
+    }
+    "#;
+
+    #[tokio::test]
+    async fn issue_label_not_in_allowlist_is_ignored() {
+        let (app, mut rx) = test_app();
+        let response = app
+            .oneshot(signed_request("issues", LABELED_ISSUE_PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn issue_label_in_allowlist_is_animated() {
+        let (mut app_state, mut rx) = test_state();
+        app_state.issue_filters = settings_from_toml(r#"labels = ["thank-you"]"#).unwrap();
+        let response = app(app_state)
+            .oneshot(signed_request("issues", LABELED_ISSUE_PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(matches!(
+            rx.try_recv(),
+            Some(ActorMessage::MotivationReceived {
+                kind: EventKind::IssueLabeled,
+                ..
+            })
+        ));
+    }
+
+    const SPONSORSHIP_PAYLOAD: &str = r#"
+    {
+      "action": "created",
+      "sponsorship": {
+        "sponsor": { "id": 2, "login": "octocat" },
+        "tier": { "name": "$30 a month", "monthly_price_in_dollars": 30, "is_one_time": false }
+      },
+      "sender": { "id": 2, "login": "octocat" }
+    }
+    "#;
+
+    #[tokio::test]
+    async fn sponsorship_animation_depends_on_the_tier() {
+        let (mut app_state, mut rx) = test_state();
+        app_state.sponsorship_tiers = serde_json::from_str(
+            r#"[
+                { "min_monthly_dollars": 1, "animation": "celebrate" },
+                { "min_monthly_dollars": 25, "animation": "release" }
+            ]"#,
+        )
+        .unwrap();
+        let response = app(app_state)
+            .oneshot(signed_request("sponsorship", SPONSORSHIP_PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            rx.try_recv(),
+            Some(ActorMessage::MotivationReceived {
+                kind: EventKind::Sponsorship,
+                animation: Some("release".to_owned()),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn routes_drop_or_pick_the_animation() {
+        let (mut app_state, mut rx) = test_state();
+        app_state.routes = serde_json::from_str(
+            r#"[
+                { "sender": "*[bot]", "drop": true },
+                { "repository": "manuelarte/*", "kind": "star", "action": "created", "animation": "celebrate" }
+            ]"#,
+        )
+        .unwrap();
+        let app = app(app_state);
+        let bot_star = STAR_PAYLOAD.replace("octocat", "dependabot[bot]");
+        let response = app
+            .clone()
+            .oneshot(signed_request("star", &bot_star))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(rx.try_recv(), None);
+
+        let response = app
+            .oneshot(signed_request("star", STAR_PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            rx.try_recv(),
+            Some(ActorMessage::MotivationReceived {
+                kind: EventKind::Star,
+                animation: Some("celebrate".to_owned()),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_sponsorship_sends_motivation_lost() {
+        let (app, mut rx) = test_app();
+        let payload = SPONSORSHIP_PAYLOAD.replace("created", "cancelled");
+        let response = app
+            .oneshot(signed_request("sponsorship", &payload))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            rx.try_recv(),
+            Some(ActorMessage::MotivationLost {
+                kind: EventKind::SponsorshipCancelled
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn star_and_watch_for_the_same_star_animate_once() {
+        let (app_state, mut rx) = test_state();
+        let watch = r#"
+        {
+          "action": "started",
+          "repository": { "id": 123456, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 1, "login": "octocat" }
+        }
+        "#;
+        let response = app(app_state.clone())
+            .oneshot(signed_request("star", STAR_PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let response = app(app_state)
+            .oneshot(signed_request("watch", watch))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(rx.try_recv().is_some());
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_workflow_run_changes_the_build_status() {
+        let (mut app_state, mut rx) = test_state();
+        app_state.ci_status = Arc::new(CiStatus::new(
+            serde_json::from_str(r#"{ "workflows": ["rust"], "branches": ["main"] }"#).unwrap(),
+        ));
+        let payload = r#"
+        {
+          "action": "completed",
+          "workflow_run": { "name": "rust", "head_branch": "main", "conclusion": "failure" },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 1, "login": "manuelarte" }
+        }
+        "#;
+        let response = app(app_state.clone())
+            .oneshot(signed_request("workflow_run", payload))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            app_state.actor_handler.build_status(),
+            Some(BuildStatus::Failing)
+        );
+
+        // the same conclusion again does not change anything
+        let response = app(app_state)
+            .oneshot(signed_request("workflow_run", payload))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn build_status_is_not_dropped_when_the_queue_is_full() {
+        let (mut app_state, mut rx) = test_state();
+        app_state.ci_status = Arc::new(CiStatus::new(
+            serde_json::from_str(r#"{ "workflows": ["rust"], "branches": ["main"] }"#).unwrap(),
+        ));
+        let response = app(app_state.clone())
+            .oneshot(signed_request("star", STAR_PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let payload = r#"
+        {
+          "action": "completed",
+          "workflow_run": { "name": "rust", "head_branch": "main", "conclusion": "failure" },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 1, "login": "manuelarte" }
+        }
+        "#;
+        let response = app(app_state.clone())
+            .oneshot(signed_request("workflow_run", payload))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            app_state.actor_handler.build_status(),
+            Some(BuildStatus::Failing)
+        );
+        assert_eq!(app_state.actor_handler.dropped_events(), 0);
+        assert_eq!(rx.try_recv().map(|msg| msg.kind()), Some(EventKind::Star));
+    }
+
+    #[tokio::test]
+    async fn star_reaching_a_milestone_is_celebrated() {
+        let (app, mut rx) = test_app();
+        let payload = STAR_PAYLOAD.replace(
+            r#""full_name": "manuelarte/motivate-me""#,
+            r#""full_name": "manuelarte/motivate-me", "stargazers_count": 50"#,
+        );
+        let response = app.oneshot(signed_request("star", &payload)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            rx.try_recv(),
+            Some(ActorMessage::MotivationReceived {
+                kind: EventKind::StarMilestone,
+                animation: None,
+            })
+        );
+    }
+
+    const GITLAB_PUSH_PAYLOAD: &str = r#"
+    {
+      "object_kind": "push",
+      "ref": "refs/heads/main",
+      "user_id": 4,
+      "user_username": "jsmith",
+      "project": { "id": 15, "name": "motivate-me", "path_with_namespace": "manuelarte/motivate-me" },
+      "total_commits_count": 1
+    }
+    "#;
+
+    fn gitlab_request(token: Option<&str>) -> Request<Body> {
+        let mut request = Request::post("/gitlab_webhook").header("X-Gitlab-Event", "Push Hook");
+        if let Some(token) = token {
+            request = request.header("X-Gitlab-Token", token);
+        }
+        request.body(Body::from(GITLAB_PUSH_PAYLOAD)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn gitlab_push_is_animated() {
+        let (app, mut rx) = test_app();
+        let response = app.oneshot(gitlab_request(Some(SECRET))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(rx.try_recv().map(|msg| msg.kind()), Some(EventKind::Push));
+    }
+
+    #[tokio::test]
+    async fn gitlab_wrong_or_missing_token_is_rejected() {
+        let (app, mut rx) = test_app();
+        for token in [None, Some("not the secret")] {
+            let response = app.clone().oneshot(gitlab_request(token)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert!(rx.try_recv().is_none());
+    }
+
+    // Gitea fork payload, `forkee` is the forked repository and `repository` the new fork
+    const GITEA_FORK_PAYLOAD: &str = r#"
+    {
+      "forkee": {
+        "id": 1,
+        "owner": {
+          "id": 1,
+          "login": "manuelarte",
+          "login_name": "",
+          "source_id": 0,
+          "full_name": "",
+          "email": "manuelarte@noreply.codeberg.org",
+          "avatar_url": "https://codeberg.org/avatars/1",
+          "html_url": "https://codeberg.org/manuelarte",
+          "language": "",
+          "is_admin": false,
+          "last_login": "0001-01-01T00:00:00Z",
+          "created": "2023-01-04T18:21:07Z",
+          "restricted": false,
+          "active": false,
+          "prohibit_login": false,
+          "location": "",
+          "website": "",
+          "description": "",
+          "visibility": "public",
+          "followers_count": 3,
+          "following_count": 0,
+          "starred_repos_count": 2,
+          "username": "manuelarte"
+        },
+        "name": "motivate-me",
+        "full_name": "manuelarte/motivate-me",
+        "description": "An app to motivate me continue coding",
+        "empty": false,
+        "private": false,
+        "fork": false,
+        "template": false,
+        "parent": null,
+        "mirror": false,
+        "size": 412,
+        "language": "Rust",
+        "languages_url": "https://codeberg.org/api/v1/repos/manuelarte/motivate-me/languages",
+        "html_url": "https://codeberg.org/manuelarte/motivate-me",
+        "url": "https://codeberg.org/api/v1/repos/manuelarte/motivate-me",
+        "link": "",
+        "ssh_url": "git@codeberg.org:manuelarte/motivate-me.git",
+        "clone_url": "https://codeberg.org/manuelarte/motivate-me.git",
+        "original_url": "",
+        "website": "",
+        "stars_count": 7,
+        "forks_count": 1,
+        "watchers_count": 1,
+        "open_issues_count": 0,
+        "open_pr_counter": 0,
+        "release_counter": 3,
+        "default_branch": "main",
+        "archived": false,
+        "created_at": "2025-06-01T10:00:00Z",
+        "updated_at": "2025-06-22T09:56:51Z",
+        "archived_at": "1970-01-01T00:00:00Z",
+        "permissions": { "admin": false, "push": false, "pull": true },
+        "has_issues": true,
+        "has_wiki": true,
+        "has_pull_requests": true,
+        "has_projects": true,
+        "has_releases": true,
+        "has_packages": true,
+        "has_actions": true,
+        "ignore_whitespace_conflicts": false,
+        "allow_merge_commits": true,
+        "allow_rebase": true,
+        "allow_rebase_explicit": true,
+        "allow_squash_merge": true,
+        "allow_rebase_update": true,
+        "default_delete_branch_after_merge": false,
+        "default_merge_style": "merge",
+        "default_allow_maintainer_edit": false,
+        "avatar_url": "",
+        "internal": false,
+        "mirror_interval": "",
+        "object_format_name": "sha1",
+        "mirror_updated": "0001-01-01T00:00:00Z",
+        "repo_transfer": null,
+        "topics": null,
+        "licenses": ["MIT"]
+      },
+      "repository": {
+        "id": 2,
+        "owner": {
+          "id": 2,
+          "login": "octocat",
+          "full_name": "",
+          "email": "octocat@noreply.codeberg.org",
+          "avatar_url": "https://codeberg.org/avatars/2",
+          "html_url": "https://codeberg.org/octocat",
+          "visibility": "public",
+          "username": "octocat"
+        },
+        "name": "motivate-me",
+        "full_name": "octocat/motivate-me",
+        "description": "An app to motivate me continue coding",
+        "empty": false,
+        "private": false,
+        "fork": true,
+        "template": false,
+        "parent": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+        "mirror": false,
+        "size": 0,
+        "language": "",
+        "html_url": "https://codeberg.org/octocat/motivate-me",
+        "url": "https://codeberg.org/api/v1/repos/octocat/motivate-me",
+        "ssh_url": "git@codeberg.org:octocat/motivate-me.git",
+        "clone_url": "https://codeberg.org/octocat/motivate-me.git",
+        "stars_count": 0,
+        "forks_count": 0,
+        "watchers_count": 1,
+        "open_issues_count": 0,
+        "release_counter": 0,
+        "default_branch": "main",
+        "archived": false,
+        "created_at": "2025-06-22T09:56:51Z",
+        "updated_at": "2025-06-22T09:56:51Z",
+        "permissions": { "admin": true, "push": true, "pull": true }
+      },
+      "sender": {
+        "id": 2,
+        "login": "octocat",
+        "full_name": "",
+        "email": "octocat@noreply.codeberg.org",
+        "avatar_url": "https://codeberg.org/avatars/2",
+        "html_url": "https://codeberg.org/octocat",
+        "visibility": "public",
+        "username": "octocat"
+      }
+    }
+    "#;
+
+    fn gitea_request(signature: &str) -> Request<Body> {
+        Request::post("/gitea_webhook")
+            .header("X-Forgejo-Event", "fork")
+            .header("X-Gitea-Event", "fork")
+            .header("X-Gitea-Signature", signature)
+            .body(Body::from(GITEA_FORK_PAYLOAD))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn gitea_fork_is_animated() {
+        let event_log = Arc::new(MemoryEventLog::default());
+        let (mut app_state, mut rx) = test_state();
+        app_state.event_log = event_log.clone();
+        let signature = sign(GITEA_FORK_PAYLOAD);
+        let signature = signature.strip_prefix("sha256=").unwrap();
+        let response = app(app_state)
+            .oneshot(gitea_request(signature))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(rx.try_recv().map(|msg| msg.kind()), Some(EventKind::Fork));
+
+        // the forked repository, not the new fork
+        let records = event_log.records.lock().unwrap();
+        let record = serde_json::to_value(&records[0]).unwrap();
+        assert_eq!(record["repository"], "manuelarte/motivate-me");
+        assert_eq!(record["sender"], "octocat");
+    }
+
+    #[tokio::test]
+    async fn gitea_signature_with_github_prefix_is_rejected() {
+        let (app, mut rx) = test_app();
+        let response = app
+            .oneshot(gitea_request(&sign(GITEA_FORK_PAYLOAD)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn bitbucket_merged_pull_request_is_animated() {
+        let (app, mut rx) = test_app();
+        let payload = r#"
+        {
+          "pullrequest": {
+            "id": 12,
+            "title": "Add a buzzer",
+            "state": "MERGED",
+            "author": { "display_name": "Contributor", "nickname": "contributor" }
+          },
+          "repository": { "name": "motivate-me", "full_name": "manuelarte/motivate-me" }
+        }
+        "#;
+        let request = Request::post("/bitbucket_webhook")
+            .header("X-Event-Key", "pullrequest:fulfilled")
+            .header("X-Hub-Signature", sign(payload))
+            .body(Body::from(payload))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            rx.try_recv().map(|msg| msg.kind()),
+            Some(EventKind::PullRequestMerged)
+        );
+    }
+
+    #[tokio::test]
+    async fn bitbucket_invalid_signature_is_rejected() {
+        let (app, mut rx) = test_app();
+        let request = Request::post("/bitbucket_webhook")
+            .header("X-Event-Key", "repo:fork")
+            .header("X-Hub-Signature", sign("something else"))
+            .body(Body::from("{}"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(rx.try_recv().is_none());
+    }
+
+    const MOTIVATE_TOKEN: &str = "motivate-token";
+    const MOTIVATE_PAYLOAD: &str =
+        r#"{ "kind": "sale", "source": "stripe", "message": "New sale!", "animation": "release" }"#;
+
+    #[tokio::test]
+    async fn motivate_with_bearer_token() {
+        let (app, mut rx) = test_app();
+        let request = Request::post("/motivate")
+            .header("Authorization", format!("Bearer {MOTIVATE_TOKEN}"))
+            .body(Body::from(MOTIVATE_PAYLOAD))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            rx.try_recv(),
+            Some(ActorMessage::MotivationReceived {
+                kind: EventKind::Custom,
+                animation: Some("release".to_owned()),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn motivate_with_signature() {
+        let (app, mut rx) = test_app();
+        let request = Request::post("/motivate")
+            .header("X-Hub-Signature-256", sign(MOTIVATE_PAYLOAD))
+            .body(Body::from(MOTIVATE_PAYLOAD))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(rx.try_recv().is_some());
+    }
+
+    #[tokio::test]
+    async fn motivate_without_valid_credentials_is_rejected() {
+        let (app, mut rx) = test_app();
+        for auth in [None, Some("Bearer wrong"), Some(MOTIVATE_TOKEN)] {
+            let mut request = Request::post("/motivate");
+            if let Some(auth) = auth {
+                request = request.header("Authorization", auth);
             }
+            let request = request.body(Body::from(MOTIVATE_PAYLOAD)).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
         }
-        None => {
-            error!("no signature found");
-            (StatusCode::BAD_REQUEST, "no signature found".to_string())
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn unstar_can_be_ignored() {
+        let (mut app_state, mut rx) = test_state();
+        app_state.ignore_unstars = true;
+        let app = app(app_state);
+        let response = app
+            .oneshot(signed_request("star", UNSTAR_PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn valid_signature_is_accepted() {
+        let (app, mut rx) = test_app();
+        let signature = sign(STAR_PAYLOAD);
+        let response = app.oneshot(star_request(Some(&signature))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(rx.try_recv().is_some());
+    }
+
+    #[tokio::test]
+    async fn full_queue_does_not_block_the_webhook() {
+        let (app, _rx) = test_app();
+        for i in 0..3 {
+            let payload = STAR_PAYLOAD.replace("octocat", &format!("stargazer-{i}"));
+            let response = app
+                .clone()
+                .oneshot(signed_request("star", &payload))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
         }
+
+        let response = app
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("motivate_me_dropped_events_total 2\n"));
+        assert!(body.contains("motivate_me_queued_events 1\n"));
+    }
+
+    #[tokio::test]
+    async fn missing_signature_is_rejected() {
+        let (app, mut rx) = test_app();
+        let response = app.oneshot(star_request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn malformed_signature_is_rejected() {
+        let (app, mut rx) = test_app();
+        let response = app
+            .oneshot(star_request(Some("sha256=not-hex")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn wrong_signature_is_rejected() {
+        let (app, mut rx) = test_app();
+        let signature = sign("another payload");
+        let response = app.oneshot(star_request(Some(&signature))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(rx.try_recv().is_none());
     }
 }
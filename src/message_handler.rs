@@ -1,23 +1,132 @@
-use tokio::sync::mpsc;
+use crate::ci_status::BuildStatus;
+use crate::event_queue::{PushOutcome, QueueSender};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
 
-#[derive(Clone, Debug)]
+/// The kind of event that triggered a message, used to pick its animation.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Star,
+    /// A star that made the repository reach a milestone.
+    StarMilestone,
+    Unstar,
+    Fork,
+    Release,
+    Prerelease,
+    PullRequestMerged,
+    /// Any other pull request event, like `opened` or closed without merging.
+    PullRequest,
+    IssueOpened,
+    IssueClosed,
+    IssueLabeled,
+    /// Any other issue event, like `edited` or `reopened`.
+    Issue,
+    IssueComment,
+    DiscussionCreated,
+    DiscussionAnswered,
+    /// A discussion answered by someone outside the team.
+    DiscussionAnsweredByCommunity,
+    DiscussionComment,
+    Sponsorship,
+    SponsorshipTierChanged,
+    SponsorshipCancelled,
+    WorkflowRun,
+    Push,
+    /// Sent to `/motivate` by anything else than a forge.
+    Custom,
+}
+
+impl EventKind {
+    /// Whether the event is bad news, sent as [`ActorMessage::MotivationLost`].
+    pub fn is_loss(self) -> bool {
+        matches!(self, EventKind::Unstar | EventKind::SponsorshipCancelled)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum ActorMessage {
-    MotivationReceived {},
+    /// `animation` names the pattern to play instead of the one mapped to `kind`.
+    MotivationReceived {
+        kind: EventKind,
+        animation: Option<String>,
+    },
+    MotivationLost {
+        kind: EventKind,
+    },
 }
 
+impl ActorMessage {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            ActorMessage::MotivationReceived { kind, .. }
+            | ActorMessage::MotivationLost { kind } => *kind,
+        }
+    }
+
+    pub fn animation(&self) -> Option<&str> {
+        match self {
+            ActorMessage::MotivationReceived { animation, .. } => animation.as_deref(),
+            ActorMessage::MotivationLost { .. } => None,
+        }
+    }
+}
+
+/// Enqueues messages for the [`crate::message_listener::MessageListener`] without waiting
+/// for it, so webhook requests are answered straight away.
 #[derive(Clone, Debug)]
 pub struct MessageHandler {
-    sender: mpsc::Sender<ActorMessage>,
+    sender: QueueSender,
+    /// The CI status, shown until it changes again. It is sent aside from the queue, as
+    /// each change is only reported once and must not be dropped when the queue is full.
+    build_status: watch::Sender<Option<BuildStatus>>,
 }
 
 impl MessageHandler {
-    pub fn new(sender: mpsc::Sender<ActorMessage>) -> Self {
-        Self { sender }
-    }
-    pub async fn motivation_received(&self) {
-        if (self.sender.send(ActorMessage::MotivationReceived {}).await).is_err() {
-            tracing::info!("receiver dropped");
-            assert!(self.sender.is_closed());
+    pub fn new(sender: QueueSender, build_status: watch::Sender<Option<BuildStatus>>) -> Self {
+        Self {
+            sender,
+            build_status,
         }
     }
+    /// Messages of a higher `priority` are played first, and dropped last.
+    pub fn motivation_received(
+        &self,
+        kind: EventKind,
+        animation: Option<&str>,
+        priority: u8,
+    ) -> PushOutcome {
+        self.send(
+            ActorMessage::MotivationReceived {
+                kind,
+                animation: animation.map(str::to_owned),
+            },
+            priority,
+        )
+    }
+    pub fn motivation_lost(&self, kind: EventKind, priority: u8) -> PushOutcome {
+        self.send(ActorMessage::MotivationLost { kind }, priority)
+    }
+    /// Never dropped, only the last status is kept.
+    pub fn build_status_changed(&self, status: BuildStatus) -> PushOutcome {
+        self.build_status.send_replace(Some(status));
+        PushOutcome::Queued
+    }
+    #[cfg(test)]
+    pub fn build_status(&self) -> Option<BuildStatus> {
+        *self.build_status.borrow()
+    }
+    fn send(&self, msg: ActorMessage, priority: u8) -> PushOutcome {
+        let outcome = self.sender.push(msg, priority);
+        tracing::debug!("message enqueued: {:?}", outcome);
+        outcome
+    }
+    /// Number of messages lost because the queue was full.
+    pub fn dropped_events(&self) -> u64 {
+        self.sender.dropped()
+    }
+    /// Number of messages waiting to be animated.
+    pub fn queued_events(&self) -> usize {
+        self.sender.len()
+    }
 }
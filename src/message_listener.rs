@@ -1,43 +1,372 @@
 use crate::Error;
-use crate::animation::Animation;
+use crate::animation::{Animation, AnimationSettings, Cancellation};
+use crate::ci_status::BuildStatus;
+use crate::event_queue::QueueReceiver;
 use crate::message_handler::ActorMessage;
+use crate::pattern::Pattern;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::watch;
+use tokio::task::{JoinError, JoinHandle};
+use tokio::time::{Instant, timeout_at};
 use tracing::instrument;
 
 #[derive(Debug)]
 pub struct MessageListener {
-    receiver: mpsc::Receiver<ActorMessage>,
+    receiver: QueueReceiver,
+    /// Its pattern is played in a loop while there is nothing else to animate.
+    build_status: watch::Receiver<Option<BuildStatus>>,
     animation: Arc<dyn Animation>,
+    settings: AnimationSettings,
+    cancellation: Cancellation,
 }
 
 impl MessageListener {
-    pub fn new(receiver: mpsc::Receiver<ActorMessage>, animation: Arc<dyn Animation>) -> Self {
+    pub fn new(
+        receiver: QueueReceiver,
+        build_status: watch::Receiver<Option<BuildStatus>>,
+        animation: Arc<dyn Animation>,
+        settings: AnimationSettings,
+    ) -> Self {
         Self {
             receiver,
+            build_status,
             animation,
+            settings,
+            cancellation: Cancellation::default(),
         }
     }
 
+    /// Handle to stop the running animation and the listener.
+    pub fn cancellation(&self) -> Cancellation {
+        self.cancellation.clone()
+    }
+
     #[instrument]
-    async fn handle_message(&mut self, msg: ActorMessage) -> Result<(), Error> {
-        match msg {
-            ActorMessage::MotivationReceived {} => {
-                tracing::info!("Running task ActorMessage::MotivationReceived...");
+    async fn handle_messages(&mut self, messages: Vec<ActorMessage>) -> Result<(), Error> {
+        // one animation per kind and animation, in the order they first arrived
+        let mut bursts: Vec<(&ActorMessage, usize)> = vec![];
+        for msg in &messages {
+            match bursts.iter_mut().find(|(first, _)| *first == msg) {
+                Some((_, count)) => *count += 1,
+                None => bursts.push((msg, 1)),
             }
         }
-
-        self.animation.animate();
-        tracing::info!("Finished task ActorMessage::MotivationReceived...");
+        for (msg, count) in bursts {
+            if self.cancellation.is_cancelled() {
+                break;
+            }
+            let kind = msg.kind();
+            tracing::info!("Running task for {count} {kind:?} event(s)...");
+            let pattern = self
+                .settings
+                .pattern_for_burst(kind, msg.animation(), count);
+            self.animate(pattern).await?;
+            tracing::info!("Finished task for {count} {kind:?} event(s)...");
+        }
         Ok(())
     }
 
+    /// Plays the animation on the blocking thread pool, so the runtime workers stay free.
+    async fn animate(&self, pattern: Pattern) -> Result<(), Error> {
+        let animation = self.animation.clone();
+        let cancellation = self.cancellation.clone();
+        tokio::task::spawn_blocking(move || animation.animate(&pattern, &cancellation))
+            .await
+            .map_err(|e| {
+                tracing::error!("animation task failed: {e}");
+                Error::GeneralError
+            })
+    }
+
+    /// Waits for the next message, playing the pattern of the CI status meanwhile. The
+    /// pattern is stopped as soon as a message arrives, and replaced when the status changes.
+    async fn next_message(&mut self) -> Result<Option<ActorMessage>, Error> {
+        let join_error = |e| {
+            tracing::error!("ambient animation task failed: {e}");
+            Error::GeneralError
+        };
+        loop {
+            if self.receiver.is_closed() {
+                return Ok(self.receiver.recv().await);
+            }
+            if let Some(msg) = self.receiver.try_recv() {
+                return Ok(Some(msg));
+            }
+            if self.cancellation.is_cancelled() {
+                return Ok(None);
+            }
+            let stop = self.cancellation.child();
+            let status = *self.build_status.borrow_and_update();
+            let mut ambient_task = status.map(|status| {
+                tracing::info!("CI status is {status:?}");
+                let ambient = self.settings.pattern_for_status(status);
+                let animation = self.animation.clone();
+                let looped = stop.clone();
+                tokio::task::spawn_blocking(move || animation.animate_looped(&ambient, &looped))
+            });
+            let msg = tokio::select! {
+                msg = self.receiver.recv() => Some(msg),
+                Ok(()) = self.build_status.changed() => None,
+                // only ends once the listener is cancelled
+                result = finished(&mut ambient_task) => {
+                    result.map_err(join_error)?;
+                    return Ok(None);
+                }
+            };
+            stop.cancel();
+            if let Some(task) = ambient_task {
+                task.await.map_err(join_error)?;
+            }
+            if let Some(msg) = msg {
+                return Ok(msg);
+            }
+        }
+    }
+
     pub async fn run(&mut self) -> Result<(), Error> {
-        while let Some(msg) = self.receiver.recv().await {
+        while let Some(msg) = self.next_message().await? {
             tracing::debug!("MessageListener: received {:?}", &msg);
-            self.handle_message(msg).await?;
+            let mut messages = vec![msg];
+            let deadline = Instant::now() + self.settings.combo_window();
+            while let Ok(Some(msg)) = timeout_at(deadline, self.receiver.recv()).await {
+                tracing::debug!("MessageListener: received {:?} in combo window", &msg);
+                messages.push(msg);
+            }
+            self.handle_messages(messages).await?;
+            if self.cancellation.is_cancelled() {
+                tracing::info!("MessageListener: cancelled");
+                break;
+            }
         }
 
         Ok(())
     }
 }
+
+/// Waits for the task to finish, forever when there is none.
+async fn finished(task: &mut Option<JoinHandle<()>>) -> Result<(), JoinError> {
+    match task {
+        Some(task) => task.await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{MockLed, SequenceAnimation};
+    use crate::ci_status::BuildStatus;
+    use crate::event_queue::{OverflowPolicy, channel};
+    use crate::message_handler::EventKind;
+    use crate::settings_from_toml;
+    use std::time::Duration;
+
+    fn listener(receiver: QueueReceiver) -> MessageListener {
+        let animation = Arc::new(SequenceAnimation::new(Arc::new(MockLed::default())));
+        MessageListener::new(
+            receiver,
+            watch::channel(None).1,
+            animation,
+            AnimationSettings::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn burst_is_played_as_one_combo() {
+        let toml = r#"
+        combo_window_ms = 100
+
+        [patterns]
+        combo = "on 1ms, off 1ms"
+        fork = "on 1ms"
+
+        [animations]
+        fork = "fork"
+        "#;
+        let settings = settings_from_toml::<AnimationSettings>(toml).unwrap();
+        let led = Arc::new(MockLed::default());
+        let animation = Arc::new(SequenceAnimation::new(led.clone()));
+        let (tx, rx) = channel(8, OverflowPolicy::DropOldest);
+        let mut listener = MessageListener::new(rx, watch::channel(None).1, animation, settings);
+
+        for kind in [
+            EventKind::Star,
+            EventKind::Fork,
+            EventKind::Star,
+            EventKind::Star,
+        ] {
+            tx.push(
+                ActorMessage::MotivationReceived {
+                    kind,
+                    animation: None,
+                },
+                0,
+            );
+        }
+        drop(tx);
+        listener.run().await.unwrap();
+
+        // three stars as a combo of three blinks, then the single fork
+        assert_eq!(
+            led.history(),
+            vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0]
+        );
+    }
+
+    #[tokio::test]
+    async fn losses_are_not_played_as_a_combo() {
+        let toml = r#"
+        combo_window_ms = 100
+
+        [patterns]
+        combo = "on 1ms, off 1ms"
+        sad = "on 1ms"
+        "#;
+        let settings = settings_from_toml::<AnimationSettings>(toml).unwrap();
+        let led = Arc::new(MockLed::default());
+        let animation = Arc::new(SequenceAnimation::new(led.clone()));
+        let (tx, rx) = channel(8, OverflowPolicy::DropOldest);
+        let mut listener = MessageListener::new(rx, watch::channel(None).1, animation, settings);
+
+        for _ in 0..2 {
+            tx.push(
+                ActorMessage::MotivationLost {
+                    kind: EventKind::Unstar,
+                },
+                0,
+            );
+        }
+        drop(tx);
+        listener.run().await.unwrap();
+
+        // the sad pattern twice, not the combo blinks
+        assert_eq!(led.history(), vec![1.0, 1.0, 0.0]);
+    }
+
+    #[tokio::test]
+    async fn animation_does_not_block_the_runtime() {
+        let (tx, rx) = channel(1, OverflowPolicy::DropOldest);
+        let mut listener = listener(rx);
+        let cancellation = listener.cancellation();
+        tokio::spawn(async move { listener.run().await });
+        tx.push(
+            ActorMessage::MotivationReceived {
+                kind: EventKind::Star,
+                animation: None,
+            },
+            0,
+        );
+
+        // single threaded runtime: this only completes if the animation is not parked on it
+        let start = Instant::now();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+        cancellation.cancel();
+    }
+
+    #[tokio::test]
+    async fn build_status_is_played_while_idle() {
+        let toml = r#"
+        [patterns]
+        build_failing = "on 1ms, off 1ms"
+        "#;
+        let settings = settings_from_toml::<AnimationSettings>(toml).unwrap();
+        let led = Arc::new(MockLed::default());
+        let animation = Arc::new(SequenceAnimation::new(led.clone()));
+        let (_tx, rx) = channel(2, OverflowPolicy::DropOldest);
+        let (status_tx, status_rx) = watch::channel(None);
+        let mut listener = MessageListener::new(rx, status_rx, animation, settings);
+        let cancellation = listener.cancellation();
+        let handle = tokio::spawn(async move { listener.run().await });
+        status_tx.send_replace(Some(BuildStatus::Failing));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancellation.cancel();
+        handle.await.unwrap().unwrap();
+
+        // the failing pattern ends at brightness 0, and is played more than once
+        assert!(led.history().iter().filter(|b| **b == 1.0).count() > 1);
+    }
+
+    #[tokio::test]
+    async fn cancellation_stops_the_listener() {
+        let (tx, rx) = channel(2, OverflowPolicy::DropOldest);
+        let mut listener = listener(rx);
+        let cancellation = listener.cancellation();
+        let handle = tokio::spawn(async move { listener.run().await });
+        for _ in 0..2 {
+            tx.push(
+                ActorMessage::MotivationReceived {
+                    kind: EventKind::Star,
+                    animation: None,
+                },
+                0,
+            );
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cancellation.cancel();
+
+        let result = tokio::time::timeout(Duration::from_secs(1), handle).await;
+        assert!(matches!(result, Ok(Ok(Ok(())))));
+    }
+
+    #[tokio::test]
+    async fn message_interrupts_the_build_status() {
+        let settings = settings_from_toml::<AnimationSettings>(
+            r#"
+            [patterns]
+            build_failing = "on 10s"
+            celebrate = "on 1ms, off 1ms"
+            "#,
+        )
+        .unwrap();
+        let led = Arc::new(MockLed::default());
+        let animation = Arc::new(SequenceAnimation::new(led.clone()));
+        let (tx, rx) = channel(2, OverflowPolicy::DropOldest);
+        let (status_tx, status_rx) = watch::channel(None);
+        let mut listener = MessageListener::new(rx, status_rx, animation, settings);
+        let cancellation = listener.cancellation();
+        let handle = tokio::spawn(async move { listener.run().await });
+        status_tx.send_replace(Some(BuildStatus::Failing));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        tx.push(
+            ActorMessage::MotivationReceived {
+                kind: EventKind::Star,
+                animation: Some("celebrate".to_owned()),
+            },
+            0,
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cancellation.cancel();
+        handle.await.unwrap().unwrap();
+
+        // the star is played straight away, then the build status again
+        assert_eq!(led.history(), vec![1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[tokio::test]
+    async fn steady_build_status_does_not_blink() {
+        let settings = settings_from_toml::<AnimationSettings>(
+            r#"
+            [patterns]
+            build_passing = "on 1ms"
+            "#,
+        )
+        .unwrap();
+        let led = Arc::new(MockLed::default());
+        let animation = Arc::new(SequenceAnimation::new(led.clone()));
+        let (_tx, rx) = channel(2, OverflowPolicy::DropOldest);
+        let (status_tx, status_rx) = watch::channel(None);
+        let mut listener = MessageListener::new(rx, status_rx, animation, settings);
+        let cancellation = listener.cancellation();
+        let handle = tokio::spawn(async move { listener.run().await });
+        status_tx.send_replace(Some(BuildStatus::Passing));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancellation.cancel();
+        handle.await.unwrap().unwrap();
+
+        let history = led.history();
+        assert!(history.len() > 2);
+        assert_eq!(history.last(), Some(&0.0));
+        assert!(history[..history.len() - 1].iter().all(|b| *b == 1.0));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::json_file::BackgroundSaver;
+use crate::payloads::Repository;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+/// Star counts worth a bigger celebration.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MilestoneSettings {
+    stars: Vec<u32>,
+    /// Every multiple of this is a milestone too.
+    every: Option<u32>,
+    /// Where the reached milestones are kept, so they survive a restart.
+    path: Option<String>,
+}
+
+impl Default for MilestoneSettings {
+    fn default() -> Self {
+        Self {
+            stars: vec![10, 50, 100, 1000],
+            every: None,
+            path: None,
+        }
+    }
+}
+
+impl MilestoneSettings {
+    /// The highest milestone at or below `stars`.
+    fn milestone_at(&self, stars: u32) -> Option<u32> {
+        let listed = self.stars.iter().copied().filter(|m| *m <= stars).max();
+        let every = self
+            .every
+            .filter(|every| *every > 0)
+            .map(|every| stars / every * every)
+            .filter(|m| *m > 0);
+        listed.max(every)
+    }
+}
+
+/// Highest milestone reached by each repository, so every milestone is celebrated once.
+#[derive(Debug)]
+pub struct Milestones {
+    settings: MilestoneSettings,
+    reached: Arc<Mutex<HashMap<String, u32>>>,
+    saver: Option<BackgroundSaver>,
+}
+
+impl Milestones {
+    /// Loads the milestones already reached from `settings.path`, if any.
+    pub fn new(settings: MilestoneSettings) -> Self {
+        let reached = match settings.path.as_deref().map(|path| (path, fs::read(path))) {
+            Some((path, Ok(content))) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                error!("milestones {path} can't be read: {e}");
+                HashMap::new()
+            }),
+            _ => HashMap::new(),
+        };
+        let saver = settings
+            .path
+            .as_deref()
+            .map(|path| BackgroundSaver::new("milestones", path));
+        Self {
+            settings,
+            reached: Arc::new(Mutex::new(reached)),
+            saver,
+        }
+    }
+
+    /// Records a star on `repository`, returning the milestone it crossed, if it crossed one
+    /// not celebrated yet. The milestone a repository is at when first seen is only
+    /// celebrated if this star is the one reaching it.
+    pub fn crossed(&self, repository: &Repository) -> Option<u32> {
+        let stars = repository.stargazers_count()?;
+        let milestone = self.settings.milestone_at(stars)?;
+        let mut reached = self.reached.lock().unwrap();
+        let previous = reached.get(repository.full_name()).copied();
+        if previous.is_some_and(|previous| milestone <= previous) {
+            return None;
+        }
+        reached.insert(repository.full_name().to_owned(), milestone);
+        drop(reached);
+        if let Some(saver) = &self.saver {
+            let reached = self.reached.clone();
+            saver.schedule(move || reached.lock().unwrap().clone());
+        }
+        let crossed = previous.is_some() || milestone == stars;
+        if crossed {
+            info!("{} reached {milestone} stars", repository.full_name());
+        }
+        crossed.then_some(milestone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository(stars: u32) -> Repository {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": "motivate-me",
+            "full_name": "manuelarte/motivate-me",
+            "stargazers_count": stars,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn listed_and_every_n_milestones() {
+        let settings = MilestoneSettings {
+            stars: vec![10, 50],
+            every: Some(100),
+            path: None,
+        };
+        assert_eq!(settings.milestone_at(9), None);
+        assert_eq!(settings.milestone_at(49), Some(10));
+        assert_eq!(settings.milestone_at(99), Some(50));
+        assert_eq!(settings.milestone_at(250), Some(200));
+    }
+
+    #[test]
+    fn milestone_is_celebrated_once() {
+        let milestones = Milestones::new(MilestoneSettings::default());
+        assert_eq!(milestones.crossed(&repository(10)), Some(10));
+        // unstarred and starred again
+        assert_eq!(milestones.crossed(&repository(10)), None);
+        assert_eq!(milestones.crossed(&repository(11)), None);
+        // a missed star webhook does not skip the milestone
+        assert_eq!(milestones.crossed(&repository(51)), Some(50));
+    }
+
+    #[test]
+    fn milestone_reached_before_is_not_celebrated() {
+        let milestones = Milestones::new(MilestoneSettings::default());
+        assert_eq!(milestones.crossed(&repository(73)), None);
+        assert_eq!(milestones.crossed(&repository(100)), Some(100));
+    }
+
+    #[test]
+    fn milestones_survive_a_restart() {
+        let path = std::env::temp_dir().join(format!("milestones-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let settings = MilestoneSettings {
+            path: Some(path.to_str().unwrap().to_owned()),
+            ..MilestoneSettings::default()
+        };
+        let milestones = Milestones::new(settings.clone());
+        assert_eq!(milestones.crossed(&repository(10)), Some(10));
+        drop(milestones);
+
+        let milestones = Milestones::new(settings);
+        assert_eq!(milestones.crossed(&repository(10)), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn milestones_are_saved_off_the_runtime() {
+        let path =
+            std::env::temp_dir().join(format!("milestones-{}-async.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let milestones = Milestones::new(MilestoneSettings {
+            path: Some(path.to_str().unwrap().to_owned()),
+            ..MilestoneSettings::default()
+        });
+        assert_eq!(milestones.crossed(&repository(10)), Some(10));
+
+        let saved = async {
+            while !fs::read(&path).is_ok_and(|content| content.starts_with(b"{")) {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        };
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_secs(5), saved)
+                .await
+                .is_ok()
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
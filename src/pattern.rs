@@ -0,0 +1,218 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Most steps a pattern may have once repeated, so a typo can't exhaust the memory.
+const MAX_STEPS: usize = 10_000;
+
+/// A single instruction of an animation pattern.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    /// Full brightness for the given time.
+    On(Duration),
+    /// LED off for the given time.
+    Off(Duration),
+    /// Raise the brightness from off to full in the given time.
+    FadeIn(Duration),
+    /// Lower the brightness from full to off in the given time.
+    FadeOut(Duration),
+}
+
+/// An animation pattern, written as a comma separated list of steps, e.g.
+/// `on 200ms, off 100ms, repeat 3, fade-in 1s`.
+///
+/// `repeat N` plays the steps written since the previous `repeat` N times in total.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternError {
+    position: usize,
+    token: String,
+    reason: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "step {} '{}': {}",
+            self.position, self.token, self.reason
+        )
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl Pattern {
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// How long playing the pattern once takes.
+    pub fn duration(&self) -> Duration {
+        self.steps
+            .iter()
+            .map(|step| match *step {
+                Step::On(d) | Step::Off(d) | Step::FadeIn(d) | Step::FadeOut(d) => d,
+            })
+            .sum()
+    }
+
+    /// This pattern played `times` times in a row.
+    pub fn repeated(&self, times: usize) -> Self {
+        Self {
+            steps: self.steps.repeat(times),
+        }
+    }
+
+    pub fn parse(input: &str) -> Result<Self, PatternError> {
+        let mut steps = vec![];
+        let mut group_start = 0;
+        for (index, token) in input.split(',').map(str::trim).enumerate() {
+            let position = index + 1;
+            let error = |reason: &str| PatternError {
+                position,
+                token: token.to_owned(),
+                reason: reason.to_owned(),
+            };
+            let (name, argument) = token
+                .split_once(char::is_whitespace)
+                .map(|(name, argument)| (name, argument.trim()))
+                .ok_or_else(|| error("expected a step and a value, e.g. 'on 200ms'"))?;
+            match name {
+                "repeat" => {
+                    let times = argument
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|times| *times > 0)
+                        .ok_or_else(|| error("repeat needs a positive number"))?;
+                    if group_start == steps.len() {
+                        return Err(error("nothing to repeat"));
+                    }
+                    let group = steps[group_start..].to_vec();
+                    if group.len().saturating_mul(times - 1) > MAX_STEPS.saturating_sub(steps.len())
+                    {
+                        return Err(error(&format!(
+                            "the pattern can't be longer than {MAX_STEPS} steps"
+                        )));
+                    }
+                    for _ in 1..times {
+                        steps.extend_from_slice(&group);
+                    }
+                    group_start = steps.len();
+                }
+                "on" | "off" | "fade-in" | "fade-out" => {
+                    let duration = parse_duration(argument).ok_or_else(|| {
+                        error("invalid duration, expected e.g. '200ms' or '1.5s'")
+                    })?;
+                    steps.push(match name {
+                        "on" => Step::On(duration),
+                        "off" => Step::Off(duration),
+                        "fade-in" => Step::FadeIn(duration),
+                        _ => Step::FadeOut(duration),
+                    });
+                }
+                _ => {
+                    return Err(error(
+                        "unknown step, expected one of on, off, fade-in, fade-out, repeat",
+                    ));
+                }
+            }
+        }
+        Ok(Self { steps })
+    }
+}
+
+fn parse_duration(input: &str) -> Option<Duration> {
+    let (value, unit) = match input.strip_suffix("ms") {
+        Some(value) => (value, 1_000.0),
+        None => (input.strip_suffix('s')?, 1.0),
+    };
+    let value = value.trim().parse::<f64>().ok()?;
+    // rejects negative, non-finite and too long durations, which `from_secs_f64` panics on
+    Duration::try_from_secs_f64(value / unit).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn parse_example_pattern() {
+        let actual = Pattern::parse("on 200ms, off 100ms, repeat 3, fade-in 1s").unwrap();
+        let expected = Pattern {
+            steps: vec![
+                Step::On(ms(200)),
+                Step::Off(ms(100)),
+                Step::On(ms(200)),
+                Step::Off(ms(100)),
+                Step::On(ms(200)),
+                Step::Off(ms(100)),
+                Step::FadeIn(ms(1000)),
+            ],
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn repeat_only_applies_since_previous_repeat() {
+        let actual = Pattern::parse("on 1s, repeat 2, fade-out 1.5s, off 0ms, repeat 2").unwrap();
+        let expected = Pattern {
+            steps: vec![
+                Step::On(ms(1000)),
+                Step::On(ms(1000)),
+                Step::FadeOut(ms(1500)),
+                Step::Off(ms(0)),
+                Step::FadeOut(ms(1500)),
+                Step::Off(ms(0)),
+            ],
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn unknown_step_is_rejected() {
+        let actual = Pattern::parse("on 200ms, blink 100ms").unwrap_err();
+        assert_eq!(
+            actual.to_string(),
+            "step 2 'blink 100ms': unknown step, expected one of on, off, fade-in, fade-out, repeat"
+        );
+    }
+
+    #[test]
+    fn invalid_duration_is_rejected() {
+        let actual = Pattern::parse("on 200").unwrap_err();
+        assert_eq!(actual.position, 1);
+        assert!(Pattern::parse("off -1s").is_err());
+        assert!(Pattern::parse("fade-in fast").is_err());
+        assert!(Pattern::parse("on 1e30s").is_err());
+        assert!(Pattern::parse("on infs").is_err());
+        assert!(Pattern::parse("on NaNms").is_err());
+    }
+
+    #[test]
+    fn invalid_repeat_is_rejected() {
+        assert!(Pattern::parse("repeat 2").is_err());
+        assert!(Pattern::parse("on 1s, repeat 0").is_err());
+        assert!(Pattern::parse("on 1s, repeat 2, repeat 2").is_err());
+        assert!(Pattern::parse("on 1s, repeat").is_err());
+        let actual = Pattern::parse("on 1ms, repeat 1000000000000").unwrap_err();
+        assert_eq!(
+            actual.to_string(),
+            "step 2 'repeat 1000000000000': the pattern can't be longer than 10000 steps"
+        );
+        assert!(Pattern::parse("on 1ms, off 1ms, repeat 5000").is_ok());
+        assert!(Pattern::parse("on 1ms, off 1ms, repeat 5001").is_err());
+    }
+
+    #[test]
+    fn empty_pattern_is_rejected() {
+        assert!(Pattern::parse("").is_err());
+    }
+}
@@ -1,58 +1,1005 @@
+use crate::message_handler::EventKind;
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 
-trait WebhookPayload {}
+pub trait WebhookPayload {
+    fn kind(&self) -> EventKind;
+    /// The repository of the event, `None` for account-wide events like sponsorships.
+    fn repository(&self) -> Option<&Repository>;
+    fn sender(&self) -> &GithubUser;
+    fn starred_at(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+    /// Whether the event should trigger an animation at all.
+    fn animates(&self) -> bool {
+        true
+    }
+    /// Relationship of the author of the pull request, issue, etc. with the repository.
+    fn author_association(&self) -> Option<&AuthorAssociation> {
+        None
+    }
+    /// The label added by a `labeled` action.
+    fn label(&self) -> Option<&str> {
+        None
+    }
+    /// Monthly amount of the sponsorship tier, for sponsorship events.
+    fn monthly_price_in_dollars(&self) -> Option<u32> {
+        None
+    }
+    /// Pattern requested by the event itself, instead of the one mapped to its kind.
+    fn animation(&self) -> Option<&str> {
+        None
+    }
+    /// The run of a `workflow_run` event, which updates the CI status instead of animating.
+    fn workflow_run(&self) -> Option<&WorkflowRun> {
+        None
+    }
+}
+
+/// The `action` of a webhook as sent, including those the payloads don't tell apart,
+/// like `reopened`.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct RawAction {
+    action: Option<String>,
+}
+
+impl RawAction {
+    pub fn action(&self) -> Option<&str> {
+        self.action.as_deref()
+    }
+}
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct Repository {
     id: i32,
     full_name: String,
     name: String,
+    /// Sent with star events, and most others, but not in every nested repository.
+    /// Gitea and Forgejo call it `stars_count`.
+    #[serde(default, alias = "stars_count")]
+    stargazers_count: Option<u32>,
+}
+
+impl Repository {
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    pub fn stargazers_count(&self) -> Option<u32> {
+        self.stargazers_count
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct GithubUser {
+    id: i32,
+    login: String,
+}
+
+impl GithubUser {
+    pub fn login(&self) -> &str {
+        &self.login
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuthorAssociation {
+    Owner,
+    Member,
+    Collaborator,
+    Contributor,
+    FirstTimeContributor,
+    FirstTimer,
+    Mannequin,
+    None,
+}
+
+impl AuthorAssociation {
+    /// Whether the author is part of the team owning the repository.
+    pub fn is_team(&self) -> bool {
+        matches!(self, AuthorAssociation::Owner | AuthorAssociation::Member)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")] // Converts enum variants to lowercase
+pub enum StarAction {
+    Created,
+    Deleted,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct StarPayload {
+    action: StarAction,
+    repository: Repository,
+    sender: GithubUser,
+    starred_at: Option<DateTime<Utc>>,
+}
+
+impl WebhookPayload for StarPayload {
+    fn kind(&self) -> EventKind {
+        match self.action {
+            StarAction::Created => EventKind::Star,
+            StarAction::Deleted => EventKind::Unstar,
+        }
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+
+    fn starred_at(&self) -> Option<DateTime<Utc>> {
+        self.starred_at
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchAction {
+    Started,
+}
+
+/// Sent together with `star` when someone stars a repository, despite its name.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct WatchPayload {
+    action: WatchAction,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl WebhookPayload for WatchPayload {
+    fn kind(&self) -> EventKind {
+        EventKind::Star
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Forkee {
+    description: Option<String>,
+    forks_count: i32,
+    full_name: String,
+    id: i32,
+    name: String,
+    private: bool,
+    #[serde(alias = "stars_count")]
+    stargazers_count: i32,
+    #[serde(default)]
+    url: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ForkPayload {
+    forkee: Forkee,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl WebhookPayload for ForkPayload {
+    fn kind(&self) -> EventKind {
+        EventKind::Fork
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+}
+
+/// Gitea and Forgejo's fork event, the other way round from GitHub's: `forkee` is the
+/// forked repository and `repository` the new fork.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct GiteaForkPayload {
+    forkee: Repository,
+    repository: Forkee,
+    sender: GithubUser,
+}
+
+impl WebhookPayload for GiteaForkPayload {
+    fn kind(&self) -> EventKind {
+        EventKind::Fork
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.forkee)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseAction {
+    Published,
+    Unpublished,
+    Created,
+    Edited,
+    Deleted,
+    Prereleased,
+    Released,
+    /// Gitea and Forgejo's `edited`.
+    Updated,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Release {
+    tag_name: String,
+    name: Option<String>,
+    prerelease: bool,
+    draft: bool,
+    author: GithubUser,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ReleasePayload {
+    action: ReleaseAction,
+    release: Release,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl WebhookPayload for ReleasePayload {
+    fn kind(&self) -> EventKind {
+        if self.release.prerelease {
+            EventKind::Prerelease
+        } else {
+            EventKind::Release
+        }
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+
+    /// Only published releases, drafts are not public yet.
+    fn animates(&self) -> bool {
+        self.action == ReleaseAction::Published && !self.release.draft
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PullRequestAction {
+    Opened,
+    Closed,
+    Reopened,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct PullRequestBase {
+    repo: Repository,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct PullRequest {
+    number: i64,
+    title: String,
+    #[serde(default)]
+    merged: bool,
+    author_association: AuthorAssociation,
+    user: GithubUser,
+    base: PullRequestBase,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct PullRequestPayload {
+    action: PullRequestAction,
+    pull_request: PullRequest,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl WebhookPayload for PullRequestPayload {
+    fn kind(&self) -> EventKind {
+        if self.action == PullRequestAction::Closed && self.pull_request.merged {
+            EventKind::PullRequestMerged
+        } else {
+            EventKind::PullRequest
+        }
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.pull_request.base.repo)
+    }
+
+    /// The author of the pull request, not whoever merged it.
+    fn sender(&self) -> &GithubUser {
+        &self.pull_request.user
+    }
+
+    /// Only merged pull requests, closing without merging is not a contribution.
+    fn animates(&self) -> bool {
+        self.kind() == EventKind::PullRequestMerged
+    }
+
+    fn author_association(&self) -> Option<&AuthorAssociation> {
+        Some(&self.pull_request.author_association)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Label {
+    name: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Issue {
+    number: i64,
+    title: String,
+    user: GithubUser,
+    author_association: AuthorAssociation,
+    #[serde(default)]
+    labels: Vec<Label>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueAction {
+    Opened,
+    Closed,
+    Labeled,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct IssuesPayload {
+    action: IssueAction,
+    issue: Issue,
+    label: Option<Label>,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl WebhookPayload for IssuesPayload {
+    fn kind(&self) -> EventKind {
+        match self.action {
+            IssueAction::Closed => EventKind::IssueClosed,
+            IssueAction::Labeled => EventKind::IssueLabeled,
+            IssueAction::Opened => EventKind::IssueOpened,
+            IssueAction::Other => EventKind::Issue,
+        }
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+
+    fn animates(&self) -> bool {
+        self.action != IssueAction::Other
+    }
+
+    fn author_association(&self) -> Option<&AuthorAssociation> {
+        Some(&self.issue.author_association)
+    }
+
+    fn label(&self) -> Option<&str> {
+        self.label.as_ref().map(|label| label.name.as_str())
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Comment {
+    id: i64,
+    user: GithubUser,
+    author_association: AuthorAssociation,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueCommentAction {
+    Created,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct IssueCommentPayload {
+    action: IssueCommentAction,
+    issue: Issue,
+    comment: Comment,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl WebhookPayload for IssueCommentPayload {
+    fn kind(&self) -> EventKind {
+        EventKind::IssueComment
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+
+    fn animates(&self) -> bool {
+        self.action == IssueCommentAction::Created
+    }
+
+    fn author_association(&self) -> Option<&AuthorAssociation> {
+        Some(&self.comment.author_association)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct DiscussionCategory {
+    name: String,
+    #[serde(default)]
+    is_answerable: bool,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Discussion {
+    number: i32,
+    title: String,
+    category: DiscussionCategory,
+    user: GithubUser,
+    author_association: AuthorAssociation,
+    answer_chosen_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscussionAction {
+    Created,
+    Answered,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct DiscussionPayload {
+    action: DiscussionAction,
+    discussion: Discussion,
+    /// The comment marked as the answer, sent with `answered`.
+    answer: Option<Comment>,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl WebhookPayload for DiscussionPayload {
+    fn kind(&self) -> EventKind {
+        match (&self.action, &self.answer) {
+            (DiscussionAction::Answered, Some(answer)) if !answer.author_association.is_team() => {
+                EventKind::DiscussionAnsweredByCommunity
+            }
+            (DiscussionAction::Answered, _) => EventKind::DiscussionAnswered,
+            _ => EventKind::DiscussionCreated,
+        }
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+
+    fn animates(&self) -> bool {
+        self.action != DiscussionAction::Other
+    }
+
+    /// The association of the answer author for `answered`, of the discussion author otherwise.
+    fn author_association(&self) -> Option<&AuthorAssociation> {
+        match &self.answer {
+            Some(answer) => Some(&answer.author_association),
+            None => Some(&self.discussion.author_association),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct DiscussionCommentPayload {
+    action: IssueCommentAction,
+    discussion: Discussion,
+    comment: Comment,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl WebhookPayload for DiscussionCommentPayload {
+    fn kind(&self) -> EventKind {
+        EventKind::DiscussionComment
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+
+    fn animates(&self) -> bool {
+        self.action == IssueCommentAction::Created
+    }
+
+    fn author_association(&self) -> Option<&AuthorAssociation> {
+        Some(&self.comment.author_association)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowConclusion {
+    Success,
+    Failure,
+    TimedOut,
+    StartupFailure,
+    /// `cancelled`, `skipped`, `neutral`, etc. say nothing about the code.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct WorkflowRun {
+    name: String,
+    head_branch: String,
+    conclusion: Option<WorkflowConclusion>,
+}
+
+impl WorkflowRun {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn head_branch(&self) -> &str {
+        &self.head_branch
+    }
+
+    /// `None` until the run is completed.
+    pub fn conclusion(&self) -> Option<WorkflowConclusion> {
+        self.conclusion
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowRunAction {
+    Completed,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct WorkflowRunPayload {
+    action: WorkflowRunAction,
+    workflow_run: WorkflowRun,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl WebhookPayload for WorkflowRunPayload {
+    fn kind(&self) -> EventKind {
+        EventKind::WorkflowRun
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+
+    /// Only completed runs, requested and in progress ones have no conclusion yet.
+    fn animates(&self) -> bool {
+        self.action == WorkflowRunAction::Completed
+    }
+
+    fn workflow_run(&self) -> Option<&WorkflowRun> {
+        Some(&self.workflow_run)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SponsorshipTier {
+    name: String,
+    monthly_price_in_dollars: u32,
+    #[serde(default)]
+    is_one_time: bool,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Sponsorship {
+    sponsor: GithubUser,
+    tier: SponsorshipTier,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct TierChange {
+    from: SponsorshipTier,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SponsorshipChanges {
+    tier: Option<TierChange>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SponsorshipAction {
+    Created,
+    Cancelled,
+    TierChanged,
+    #[serde(other)]
+    Other,
+}
+
+/// Sent by GitHub Sponsors to the sponsored account, usually without a repository.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SponsorshipPayload {
+    action: SponsorshipAction,
+    sponsorship: Sponsorship,
+    changes: Option<SponsorshipChanges>,
+    repository: Option<Repository>,
+    sender: GithubUser,
+}
+
+impl SponsorshipPayload {
+    /// Whether a `tier_changed` action moved to a cheaper tier.
+    fn is_downgrade(&self) -> bool {
+        self.changes
+            .as_ref()
+            .and_then(|changes| changes.tier.as_ref())
+            .is_some_and(|change| {
+                change.from.monthly_price_in_dollars
+                    > self.sponsorship.tier.monthly_price_in_dollars
+            })
+    }
+}
+
+impl WebhookPayload for SponsorshipPayload {
+    fn kind(&self) -> EventKind {
+        match self.action {
+            SponsorshipAction::Cancelled => EventKind::SponsorshipCancelled,
+            SponsorshipAction::TierChanged => EventKind::SponsorshipTierChanged,
+            SponsorshipAction::Created | SponsorshipAction::Other => EventKind::Sponsorship,
+        }
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        self.repository.as_ref()
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sponsorship.sponsor
+    }
+
+    /// New sponsorships, cancellations and upgrades animate, downgrades and pending
+    /// changes do not.
+    fn animates(&self) -> bool {
+        match self.action {
+            SponsorshipAction::Created | SponsorshipAction::Cancelled => true,
+            SponsorshipAction::TierChanged => !self.is_downgrade(),
+            SponsorshipAction::Other => false,
+        }
+    }
+
+    fn monthly_price_in_dollars(&self) -> Option<u32> {
+        Some(self.sponsorship.tier.monthly_price_in_dollars)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct GitlabProject {
+    id: i32,
+    name: String,
+    path_with_namespace: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct GitlabPushHook {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    user_id: i32,
+    user_username: String,
+    project: GitlabProject,
+    total_commits_count: u32,
+}
+
+/// GitLab `Push Hook`, with the project and the pusher in the same shape as GitHub's.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(from = "GitlabPushHook")]
+pub struct GitlabPushPayload {
+    git_ref: String,
+    total_commits_count: u32,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl From<GitlabPushHook> for GitlabPushPayload {
+    fn from(hook: GitlabPushHook) -> Self {
+        Self {
+            git_ref: hook.git_ref,
+            total_commits_count: hook.total_commits_count,
+            repository: Repository {
+                id: hook.project.id,
+                full_name: hook.project.path_with_namespace,
+                name: hook.project.name,
+                stargazers_count: None,
+            },
+            sender: GithubUser {
+                id: hook.user_id,
+                login: hook.user_username,
+            },
+        }
+    }
+}
+
+impl WebhookPayload for GitlabPushPayload {
+    fn kind(&self) -> EventKind {
+        EventKind::Push
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+
+    /// Deleting a branch is a push without commits.
+    fn animates(&self) -> bool {
+        self.total_commits_count > 0
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct BitbucketAccount {
+    #[serde(default)]
+    nickname: Option<String>,
+    display_name: String,
+}
+
+impl From<BitbucketAccount> for GithubUser {
+    /// Bitbucket accounts have no numeric id, only a UUID.
+    fn from(account: BitbucketAccount) -> Self {
+        Self {
+            id: 0,
+            login: account.nickname.unwrap_or(account.display_name),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct BitbucketRepository {
+    full_name: String,
+    name: String,
+}
+
+impl From<BitbucketRepository> for Repository {
+    fn from(repository: BitbucketRepository) -> Self {
+        Self {
+            id: 0,
+            full_name: repository.full_name,
+            name: repository.name,
+            stargazers_count: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct BitbucketFork {
+    actor: BitbucketAccount,
+    repository: BitbucketRepository,
+    fork: BitbucketRepository,
+}
+
+/// Bitbucket Cloud `repo:fork`.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(from = "BitbucketFork")]
+pub struct BitbucketForkPayload {
+    fork: Repository,
+    repository: Repository,
+    sender: GithubUser,
+}
+
+impl From<BitbucketFork> for BitbucketForkPayload {
+    fn from(event: BitbucketFork) -> Self {
+        Self {
+            fork: event.fork.into(),
+            repository: event.repository.into(),
+            sender: event.actor.into(),
+        }
+    }
+}
+
+impl WebhookPayload for BitbucketForkPayload {
+    fn kind(&self) -> EventKind {
+        EventKind::Fork
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    fn sender(&self) -> &GithubUser {
+        &self.sender
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct BitbucketPullRequest {
+    id: i64,
+    title: String,
+    state: String,
+    author: BitbucketAccount,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct BitbucketPullRequestFulfilled {
+    pullrequest: BitbucketPullRequest,
+    repository: BitbucketRepository,
+}
+
+/// Bitbucket Cloud `pullrequest:fulfilled`, sent when a pull request is merged.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(from = "BitbucketPullRequestFulfilled")]
+pub struct BitbucketPullRequestPayload {
+    number: i64,
+    title: String,
+    state: String,
+    repository: Repository,
+    author: GithubUser,
+}
+
+impl From<BitbucketPullRequestFulfilled> for BitbucketPullRequestPayload {
+    fn from(event: BitbucketPullRequestFulfilled) -> Self {
+        Self {
+            number: event.pullrequest.id,
+            title: event.pullrequest.title,
+            state: event.pullrequest.state,
+            repository: event.repository.into(),
+            author: event.pullrequest.author.into(),
+        }
+    }
+}
+
+impl WebhookPayload for BitbucketPullRequestPayload {
+    fn kind(&self) -> EventKind {
+        EventKind::PullRequestMerged
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(&self.repository)
+    }
+
+    /// The author of the pull request, not whoever merged it.
+    fn sender(&self) -> &GithubUser {
+        &self.author
+    }
+
+    fn animates(&self) -> bool {
+        self.state == "MERGED"
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
-pub struct GithubUser {
-    id: i32,
-    login: String,
+struct MotivateRequest {
+    kind: String,
+    source: String,
+    message: String,
+    animation: Option<String>,
 }
 
+/// Sent to `/motivate` by anything that is not a forge: a sale, a form, a CI job...
 #[derive(Debug, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")] // Converts enum variants to lowercase
-pub enum StarAction {
-    Created,
-    Deleted,
+#[serde(from = "MotivateRequest")]
+pub struct MotivatePayload {
+    kind: String,
+    message: String,
+    animation: Option<String>,
+    source: GithubUser,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-pub struct StarPayload {
-    action: StarAction,
-    repository: Repository,
-    sender: GithubUser,
-    starred_at: Option<DateTime<Utc>>,
+impl From<MotivateRequest> for MotivatePayload {
+    fn from(request: MotivateRequest) -> Self {
+        Self {
+            kind: request.kind,
+            message: request.message,
+            animation: request.animation,
+            source: GithubUser {
+                id: 0,
+                login: request.source,
+            },
+        }
+    }
 }
 
-impl WebhookPayload for StarPayload {}
+impl WebhookPayload for MotivatePayload {
+    fn kind(&self) -> EventKind {
+        EventKind::Custom
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        None
+    }
+
+    /// The `source` of the request.
+    fn sender(&self) -> &GithubUser {
+        &self.source
+    }
+
+    /// The requested animation, otherwise the pattern named like the kind, if there is one.
+    fn animation(&self) -> Option<&str> {
+        Some(self.animation.as_deref().unwrap_or(&self.kind))
+    }
+}
 
 #[derive(Debug, Deserialize, PartialEq)]
-pub struct Forkee {
-    description: String,
-    forks_count: i32,
-    full_name: String,
-    id: i32,
-    name: String,
-    private: bool,
-    stargazers_count: i32,
-    url: String,
+pub struct HookConfig {
+    content_type: Option<String>,
+    url: Option<String>,
+    insecure_ssl: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
-pub struct ForkPayload {
-    forkee: Forkee,
-    repository: Repository,
-    sender: GithubUser,
+pub struct Hook {
+    id: i64,
+    active: bool,
+    events: Vec<String>,
+    config: HookConfig,
+}
+
+/// Sent by GitHub once, right after a webhook is created.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct PingPayload {
+    zen: String,
+    hook_id: i64,
+    hook: Hook,
 }
 
-impl WebhookPayload for ForkPayload {}
+impl PingPayload {
+    pub fn events(&self) -> &[String] {
+        &self.hook.events
+    }
+
+    /// Returns the `handled` events this hook is not subscribed to.
+    /// A hook subscribed to `*` receives every event.
+    pub fn missing_events<'a>(&self, handled: &[&'a str]) -> Vec<&'a str> {
+        if self.hook.events.iter().any(|e| e == "*") {
+            return vec![];
+        }
+        handled
+            .iter()
+            .filter(|h| !self.hook.events.iter().any(|e| e == *h))
+            .copied()
+            .collect()
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -81,6 +1028,7 @@ mod tests {
             id: 123456,
             full_name: "manuelarte/motivate-me".to_owned(),
             name: "motivate-me".to_owned(),
+            stargazers_count: None,
         };
         let sender = GithubUser {
             id: 1,
@@ -122,7 +1070,7 @@ mod tests {
         "#;
         let actual: ForkPayload = serde_json::from_str(json).unwrap();
         let forkee: Forkee = Forkee {
-            description: "motivate-me repo".to_owned(),
+            description: Some("motivate-me repo".to_owned()),
             forks_count: 1,
             full_name: "octocat/motivate-me".to_owned(),
             id: 654321,
@@ -135,6 +1083,7 @@ mod tests {
             id: 123456,
             full_name: "manuelarte/motivate-me".to_owned(),
             name: "motivate-me".to_owned(),
+            stargazers_count: None,
         };
         let sender = GithubUser {
             id: 1,
@@ -148,6 +1097,648 @@ mod tests {
         assert_eq!(actual, expected)
     }
 
+    #[test]
+    fn deserialize_ping_payload() {
+        let json = r#"
+        {
+          "zen": "Keep it logically awesome.",
+          "hook_id": 42,
+          "hook": {
+            "type": "Repository",
+            "id": 42,
+            "name": "web",
+            "active": true,
+            "events": ["star"],
+            "config": {
+              "content_type": "json",
+              "insecure_ssl": "0",
+              "url": "https://example.com/github_webhook"
+            }
+          }
+        }
+        "#;
+        let actual: PingPayload = serde_json::from_str(json).unwrap();
+        let expected = PingPayload {
+            zen: "Keep it logically awesome.".to_owned(),
+            hook_id: 42,
+            hook: Hook {
+                id: 42,
+                active: true,
+                events: vec!["star".to_owned()],
+                config: HookConfig {
+                    content_type: Some("json".to_owned()),
+                    url: Some("https://example.com/github_webhook".to_owned()),
+                    insecure_ssl: Some("0".to_owned()),
+                },
+            },
+        };
+        assert_eq!(actual, expected);
+        assert_eq!(actual.missing_events(&["star", "fork"]), vec!["fork"]);
+    }
+
+    #[test]
+    fn ping_payload_wildcard_subscribes_to_everything() {
+        let json = r#"
+        {
+          "zen": "Design for failure.",
+          "hook_id": 1,
+          "hook": { "id": 1, "active": true, "events": ["*"], "config": {} }
+        }
+        "#;
+        let actual: PingPayload = serde_json::from_str(json).unwrap();
+        assert!(actual.missing_events(&["star", "fork"]).is_empty());
+    }
+
+    #[test]
+    fn deserialize_release_payload() {
+        let json = r#"
+        {
+          "action": "published",
+          "release": {
+            "id": 1,
+            "tag_name": "v0.2.0",
+            "name": "v0.2.0",
+            "draft": false,
+            "prerelease": true,
+            "author": { "id": 5348899, "login": "manuelarte" }
+          },
+          "repository": {
+            "id": 123456,
+            "name": "motivate-me",
+            "full_name": "manuelarte/motivate-me"
+          },
+          "sender": { "id": 5348899, "login": "manuelarte" }
+        }
+        "#;
+        let actual: ReleasePayload = serde_json::from_str(json).unwrap();
+        let author = GithubUser {
+            id: 5348899,
+            login: "manuelarte".to_owned(),
+        };
+        let expected = ReleasePayload {
+            action: ReleaseAction::Published,
+            release: Release {
+                tag_name: "v0.2.0".to_owned(),
+                name: Some("v0.2.0".to_owned()),
+                prerelease: true,
+                draft: false,
+                author,
+            },
+            repository: Repository {
+                id: 123456,
+                full_name: "manuelarte/motivate-me".to_owned(),
+                name: "motivate-me".to_owned(),
+                stargazers_count: None,
+            },
+            sender: GithubUser {
+                id: 5348899,
+                login: "manuelarte".to_owned(),
+            },
+        };
+        assert_eq!(actual, expected);
+        assert_eq!(actual.kind(), EventKind::Prerelease);
+        assert!(actual.animates());
+    }
+
+    #[test]
+    fn release_drafts_do_not_animate() {
+        let json = r#"
+        {
+          "action": "published",
+          "release": {
+            "tag_name": "v1.0.0",
+            "name": null,
+            "draft": true,
+            "prerelease": false,
+            "author": { "id": 1, "login": "octocat" }
+          },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 1, "login": "octocat" }
+        }
+        "#;
+        let actual: ReleasePayload = serde_json::from_str(json).unwrap();
+        assert_eq!(actual.kind(), EventKind::Release);
+        assert!(!actual.animates());
+    }
+
+    #[test]
+    fn deserialize_pull_request_payload() {
+        let json = r#"
+        {
+          "action": "closed",
+          "number": 7,
+          "pull_request": {
+            "id": 1,
+            "number": 7,
+            "title": "Fix typo",
+            "state": "closed",
+            "merged": true,
+            "author_association": "FIRST_TIME_CONTRIBUTOR",
+            "user": { "id": 2, "login": "contributor" },
+            "base": {
+              "ref": "main",
+              "repo": { "id": 123456, "name": "motivate-me", "full_name": "manuelarte/motivate-me" }
+            }
+          },
+          "repository": { "id": 123456, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 5348899, "login": "manuelarte" }
+        }
+        "#;
+        let actual: PullRequestPayload = serde_json::from_str(json).unwrap();
+        let repository = || Repository {
+            id: 123456,
+            full_name: "manuelarte/motivate-me".to_owned(),
+            name: "motivate-me".to_owned(),
+            stargazers_count: None,
+        };
+        let expected = PullRequestPayload {
+            action: PullRequestAction::Closed,
+            pull_request: PullRequest {
+                number: 7,
+                title: "Fix typo".to_owned(),
+                merged: true,
+                author_association: AuthorAssociation::FirstTimeContributor,
+                user: GithubUser {
+                    id: 2,
+                    login: "contributor".to_owned(),
+                },
+                base: PullRequestBase { repo: repository() },
+            },
+            repository: repository(),
+            sender: GithubUser {
+                id: 5348899,
+                login: "manuelarte".to_owned(),
+            },
+        };
+        assert_eq!(actual, expected);
+        assert!(actual.animates());
+        assert_eq!(actual.kind(), EventKind::PullRequestMerged);
+        assert_eq!(actual.sender().login(), "contributor");
+        assert!(!actual.author_association().unwrap().is_team());
+    }
+
+    #[test]
+    fn pull_request_closed_without_merge_does_not_animate() {
+        let json = r#"
+        {
+          "action": "closed",
+          "pull_request": {
+            "number": 8,
+            "title": "Not this time",
+            "merged": false,
+            "author_association": "NONE",
+            "user": { "id": 2, "login": "contributor" },
+            "base": { "repo": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" } }
+          },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 2, "login": "contributor" }
+        }
+        "#;
+        let actual: PullRequestPayload = serde_json::from_str(json).unwrap();
+        assert!(!actual.animates());
+        assert_eq!(actual.kind(), EventKind::PullRequest);
+    }
+
+    #[test]
+    fn other_issue_action_is_not_an_opened_issue() {
+        let json = r#"
+        {
+          "action": "reopened",
+          "issue": {
+            "number": 3,
+            "title": "Add a buzzer",
+            "user": { "id": 2, "login": "contributor" },
+            "author_association": "NONE",
+            "labels": []
+          },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 2, "login": "contributor" }
+        }
+        "#;
+        let actual: IssuesPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(actual.kind(), EventKind::Issue);
+        assert!(!actual.animates());
+    }
+
+    #[test]
+    fn deserialize_issues_payload() {
+        let json = r#"
+        {
+          "action": "labeled",
+          "issue": {
+            "number": 3,
+            "title": "Add a buzzer",
+            "user": { "id": 2, "login": "contributor" },
+            "author_association": "NONE",
+            "labels": [{ "id": 10, "name": "good first issue", "color": "7057ff" }]
+          },
+          "label": { "id": 10, "name": "good first issue", "color": "7057ff" },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 5348899, "login": "manuelarte" }
+        }
+        "#;
+        let actual: IssuesPayload = serde_json::from_str(json).unwrap();
+        let label = || Label {
+            name: "good first issue".to_owned(),
+        };
+        let expected = IssuesPayload {
+            action: IssueAction::Labeled,
+            issue: Issue {
+                number: 3,
+                title: "Add a buzzer".to_owned(),
+                user: GithubUser {
+                    id: 2,
+                    login: "contributor".to_owned(),
+                },
+                author_association: AuthorAssociation::None,
+                labels: vec![label()],
+            },
+            label: Some(label()),
+            repository: Repository {
+                id: 1,
+                full_name: "manuelarte/motivate-me".to_owned(),
+                name: "motivate-me".to_owned(),
+                stargazers_count: None,
+            },
+            sender: GithubUser {
+                id: 5348899,
+                login: "manuelarte".to_owned(),
+            },
+        };
+        assert_eq!(actual, expected);
+        assert_eq!(actual.kind(), EventKind::IssueLabeled);
+        assert_eq!(actual.label(), Some("good first issue"));
+    }
+
+    #[test]
+    fn deserialize_issue_comment_payload() {
+        let json = r#"
+        {
+          "action": "created",
+          "issue": {
+            "number": 3,
+            "title": "Add a buzzer",
+            "user": { "id": 2, "login": "contributor" },
+            "author_association": "NONE"
+          },
+          "comment": {
+            "id": 99,
+            "body": "Thank you!",
+            "user": { "id": 3, "login": "fan" },
+            "author_association": "CONTRIBUTOR"
+          },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 3, "login": "fan" }
+        }
+        "#;
+        let actual: IssueCommentPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(actual.kind(), EventKind::IssueComment);
+        assert!(actual.animates());
+        assert_eq!(
+            actual.author_association(),
+            Some(&AuthorAssociation::Contributor)
+        );
+    }
+
+    const ANSWERED_DISCUSSION_PAYLOAD: &str = r#"
+    {
+      "action": "answered",
+      "discussion": {
+        "number": 7,
+        "title": "How do I wire the LED?",
+        "category": { "id": 1, "name": "Q&A", "slug": "q-a", "is_answerable": true },
+        "state": "open",
+        "answer_chosen_at": "2025-06-22T10:00:00Z",
+        "user": { "id": 1, "login": "manuelarte" },
+        "author_association": "OWNER"
+      },
+      "answer": {
+        "id": 42,
+        "user": { "id": 2, "login": "helper" },
+        "author_association": "CONTRIBUTOR"
+      },
+      "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+      "sender": { "id": 1, "login": "manuelarte" }
+    }
+    "#;
+
+    #[test]
+    fn deserialize_discussion_payload() {
+        let payload: DiscussionPayload = serde_json::from_str(ANSWERED_DISCUSSION_PAYLOAD).unwrap();
+        assert_eq!(payload.kind(), EventKind::DiscussionAnsweredByCommunity);
+        assert_eq!(payload.discussion.category.name, "Q&A");
+        assert!(payload.discussion.answer_chosen_at.is_some());
+        assert_eq!(
+            payload.author_association(),
+            Some(&AuthorAssociation::Contributor)
+        );
+        assert!(payload.animates());
+    }
+
+    #[test]
+    fn discussion_answered_by_the_team() {
+        let json = ANSWERED_DISCUSSION_PAYLOAD.replace("CONTRIBUTOR", "MEMBER");
+        let payload: DiscussionPayload = serde_json::from_str(&json).unwrap();
+        assert_eq!(payload.kind(), EventKind::DiscussionAnswered);
+    }
+
+    #[test]
+    fn deserialize_discussion_comment_payload() {
+        let json = r#"
+        {
+          "action": "created",
+          "discussion": {
+            "number": 7,
+            "title": "How do I wire the LED?",
+            "category": { "name": "General" },
+            "answer_chosen_at": null,
+            "user": { "id": 1, "login": "manuelarte" },
+            "author_association": "OWNER"
+          },
+          "comment": {
+            "id": 43,
+            "user": { "id": 2, "login": "helper" },
+            "author_association": "NONE"
+          },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 2, "login": "helper" }
+        }
+        "#;
+        let payload: DiscussionCommentPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.kind(), EventKind::DiscussionComment);
+        assert_eq!(payload.discussion.answer_chosen_at, None);
+        assert!(payload.animates());
+    }
+
+    #[test]
+    fn deserialize_gitlab_push_payload() {
+        let json = r#"
+        {
+          "object_kind": "push",
+          "event_name": "push",
+          "before": "95790bf891e76fee5e1747ab589903a6a1f80f22",
+          "after": "da1560886d4f094c3e6c9ef40349f7d38b5d27d7",
+          "ref": "refs/heads/main",
+          "user_id": 4,
+          "user_name": "John Smith",
+          "user_username": "jsmith",
+          "project_id": 15,
+          "project": {
+            "id": 15,
+            "name": "motivate-me",
+            "path_with_namespace": "manuelarte/motivate-me",
+            "default_branch": "main"
+          },
+          "commits": [],
+          "total_commits_count": 2,
+          "repository": { "name": "motivate-me", "url": "git@example.com:manuelarte/motivate-me.git" }
+        }
+        "#;
+        let payload: GitlabPushPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.kind(), EventKind::Push);
+        assert_eq!(
+            payload.repository().map(Repository::full_name),
+            Some("manuelarte/motivate-me")
+        );
+        assert_eq!(payload.sender().login(), "jsmith");
+        assert_eq!(payload.git_ref, "refs/heads/main");
+        assert!(payload.animates());
+    }
+
+    #[test]
+    fn deserialize_gitea_fork_payload() {
+        let json = r#"
+        {
+          "forkee": {
+            "id": 1,
+            "name": "motivate-me",
+            "full_name": "manuelarte/motivate-me",
+            "description": "",
+            "private": false,
+            "fork": false,
+            "stars_count": 7,
+            "forks_count": 1
+          },
+          "repository": {
+            "id": 2,
+            "name": "motivate-me",
+            "full_name": "octocat/motivate-me",
+            "description": "",
+            "private": false,
+            "fork": true,
+            "stars_count": 0,
+            "forks_count": 0
+          },
+          "sender": { "id": 2, "login": "octocat", "username": "octocat" }
+        }
+        "#;
+        let payload: GiteaForkPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.kind(), EventKind::Fork);
+        assert_eq!(
+            payload.repository().map(Repository::full_name),
+            Some("manuelarte/motivate-me")
+        );
+        assert_eq!(payload.repository.full_name, "octocat/motivate-me");
+        assert_eq!(payload.sender().login(), "octocat");
+    }
+
+    #[test]
+    fn deserialize_gitea_release_payload() {
+        let json = r#"
+        {
+          "action": "updated",
+          "release": {
+            "id": 1,
+            "tag_name": "v1.0.0",
+            "name": "v1.0.0",
+            "draft": false,
+            "prerelease": false,
+            "author": { "id": 1, "login": "manuelarte", "username": "manuelarte" }
+          },
+          "repository": {
+            "id": 1,
+            "name": "motivate-me",
+            "full_name": "manuelarte/motivate-me",
+            "stars_count": 7
+          },
+          "sender": { "id": 1, "login": "manuelarte", "username": "manuelarte" }
+        }
+        "#;
+        let payload: ReleasePayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.action, ReleaseAction::Updated);
+        assert!(!payload.animates());
+        assert_eq!(payload.repository.stargazers_count(), Some(7));
+    }
+
+    #[test]
+    fn deserialize_bitbucket_fork_payload() {
+        let json = r#"
+        {
+          "actor": {
+            "type": "user",
+            "display_name": "Octo Cat",
+            "nickname": "octocat",
+            "uuid": "{d301aafa-d676-4ee0-88be-962be7417567}"
+          },
+          "repository": {
+            "type": "repository",
+            "name": "motivate-me",
+            "full_name": "manuelarte/motivate-me",
+            "uuid": "{a1b2c3d4-0000-0000-0000-000000000001}"
+          },
+          "fork": {
+            "type": "repository",
+            "name": "motivate-me",
+            "full_name": "octocat/motivate-me",
+            "uuid": "{a1b2c3d4-0000-0000-0000-000000000002}"
+          }
+        }
+        "#;
+        let payload: BitbucketForkPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.kind(), EventKind::Fork);
+        assert_eq!(payload.sender().login(), "octocat");
+        assert_eq!(payload.fork.full_name(), "octocat/motivate-me");
+        assert_eq!(
+            payload.repository().map(Repository::full_name),
+            Some("manuelarte/motivate-me")
+        );
+    }
+
+    #[test]
+    fn deserialize_bitbucket_pull_request_payload() {
+        let json = r#"
+        {
+          "actor": { "display_name": "Manuel", "nickname": "manuelarte" },
+          "pullrequest": {
+            "id": 12,
+            "title": "Add a buzzer",
+            "state": "MERGED",
+            "author": { "display_name": "Contributor" },
+            "destination": {
+              "branch": { "name": "main" },
+              "repository": { "name": "motivate-me", "full_name": "manuelarte/motivate-me" }
+            }
+          },
+          "repository": { "name": "motivate-me", "full_name": "manuelarte/motivate-me" }
+        }
+        "#;
+        let payload: BitbucketPullRequestPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.kind(), EventKind::PullRequestMerged);
+        assert_eq!(payload.sender().login(), "Contributor");
+        assert!(payload.animates());
+    }
+
+    #[test]
+    fn deserialize_motivate_payload() {
+        let json = r#"{ "kind": "sale", "source": "stripe", "message": "New sale!" }"#;
+        let payload: MotivatePayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.kind(), EventKind::Custom);
+        assert_eq!(payload.sender().login(), "stripe");
+        assert_eq!(payload.animation(), Some("sale"));
+
+        let json =
+            r#"{ "kind": "sale", "source": "stripe", "message": "", "animation": "release" }"#;
+        let payload: MotivatePayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.animation(), Some("release"));
+    }
+
+    #[test]
+    fn deserialize_watch_payload() {
+        let json = r#"
+        {
+          "action": "started",
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 2, "login": "octocat" }
+        }
+        "#;
+        let payload: WatchPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.kind(), EventKind::Star);
+        assert_eq!(payload.sender().login(), "octocat");
+    }
+
+    #[test]
+    fn deserialize_workflow_run_payload() {
+        let json = r#"
+        {
+          "action": "completed",
+          "workflow_run": {
+            "id": 30433642,
+            "name": "rust",
+            "head_branch": "main",
+            "head_sha": "acb5820ced9479c074f688cc328bf03f341a511d",
+            "event": "push",
+            "status": "completed",
+            "conclusion": "timed_out",
+            "run_number": 562
+          },
+          "workflow": { "id": 159038, "name": "rust", "path": ".github/workflows/rust.yml" },
+          "repository": { "id": 1, "name": "motivate-me", "full_name": "manuelarte/motivate-me" },
+          "sender": { "id": 1, "login": "manuelarte" }
+        }
+        "#;
+        let payload: WorkflowRunPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.kind(), EventKind::WorkflowRun);
+        assert!(payload.animates());
+        let run = payload.workflow_run().unwrap();
+        assert_eq!(run.name(), "rust");
+        assert_eq!(run.head_branch(), "main");
+        assert_eq!(run.conclusion(), Some(WorkflowConclusion::TimedOut));
+    }
+
+    const SPONSORSHIP_PAYLOAD: &str = r#"
+    {
+      "action": "tier_changed",
+      "sponsorship": {
+        "node_id": "MDExOlNwb25zb3JzaGlwMQ==",
+        "created_at": "2025-06-22T09:56:51Z",
+        "sponsorable": { "id": 1, "login": "manuelarte" },
+        "sponsor": { "id": 2, "login": "octocat" },
+        "privacy_level": "public",
+        "tier": {
+          "node_id": "MDEyOlNwb25zb3JzVGllcjE=",
+          "name": "$25 a month",
+          "monthly_price_in_cents": 2500,
+          "monthly_price_in_dollars": 25,
+          "is_one_time": false,
+          "is_custom_amount": false
+        }
+      },
+      "changes": {
+        "tier": {
+          "from": {
+            "node_id": "MDEyOlNwb25zb3JzVGllcjA=",
+            "name": "$5 a month",
+            "monthly_price_in_cents": 500,
+            "monthly_price_in_dollars": 5,
+            "is_one_time": false,
+            "is_custom_amount": false
+          }
+        }
+      },
+      "sender": { "id": 2, "login": "octocat" }
+    }
+    "#;
+
+    #[test]
+    fn deserialize_sponsorship_payload() {
+        let payload: SponsorshipPayload = serde_json::from_str(SPONSORSHIP_PAYLOAD).unwrap();
+        assert_eq!(payload.kind(), EventKind::SponsorshipTierChanged);
+        assert_eq!(payload.sender().login(), "octocat");
+        assert_eq!(payload.repository(), None);
+        assert_eq!(payload.monthly_price_in_dollars(), Some(25));
+        assert!(payload.animates());
+    }
+
+    #[test]
+    fn sponsorship_downgrade_does_not_animate() {
+        let json = SPONSORSHIP_PAYLOAD.replace(
+            r#""monthly_price_in_dollars": 25"#,
+            r#""monthly_price_in_dollars": 1"#,
+        );
+        let payload: SponsorshipPayload = serde_json::from_str(&json).unwrap();
+        assert!(!payload.animates());
+    }
+
     #[test]
     fn real_star_example_test() {
         let json = r#"
@@ -282,6 +1873,18 @@ mod tests {
         if let Err(e) = &actual {
             println!("{}", e)
         }
-        assert!(actual.is_ok())
+        assert!(actual.is_ok());
+        let repository = actual.unwrap().repository;
+        assert_eq!(repository.stargazers_count(), Some(1));
+    }
+
+    #[test]
+    fn raw_action_keeps_actions_the_payload_does_not_tell_apart() {
+        let body = r#"{ "action": "reopened", "issue": { "number": 1 } }"#;
+        let raw: RawAction = serde_json::from_str(body).unwrap();
+        assert_eq!(raw.action(), Some("reopened"));
+
+        let raw: RawAction = serde_json::from_str(r#"{ "object_kind": "push" }"#).unwrap();
+        assert_eq!(raw.action(), None);
     }
 }
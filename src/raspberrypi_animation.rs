@@ -1,37 +1,46 @@
-use crate::animation::Animation;
-use rppal::gpio::Gpio;
+use crate::animation::LedBackend;
+use rppal::gpio::{Gpio, OutputPin};
 use rppal::system::DeviceInfo;
-use std::thread;
-use std::time::Duration;
+use std::sync::Mutex;
 use tracing::{debug, trace};
 
+// Gpio uses BCM pin numbering. BCM GPIO 23 is tied to physical pin 16.
+const GPIO_LED: u8 = 23;
+// Software PWM frequency used to dim the LED.
+const PWM_FREQUENCY: f64 = 100.0;
+
 #[derive(Debug)]
-pub struct RaspberryPiAnimation {}
+pub struct RaspberryPiLed {
+    pin: Mutex<OutputPin>,
+}
 
-impl RaspberryPiAnimation {
+impl RaspberryPiLed {
     pub fn new() -> Self {
         debug!(
             "Blinking a LED on a {}.",
             DeviceInfo::new().unwrap().model()
         );
-        Self {}
+        let pin = Gpio::new().unwrap().get(GPIO_LED).unwrap().into_output();
+        Self {
+            pin: Mutex::new(pin),
+        }
     }
 }
 
-impl Animation for RaspberryPiAnimation {
-    fn animate(&self) {
-        // Gpio uses BCM pin numbering. BCM GPIO 23 is tied to physical pin 16.
-        const GPIO_LED: u8 = 23;
-
-        let mut pin = Gpio::new().unwrap().get(GPIO_LED).unwrap().into_output();
-        for _ in 1..20 {
-            // Blink the LED by setting the pin's logic level high for 500 ms.
+impl LedBackend for RaspberryPiLed {
+    fn set_brightness(&self, brightness: f64) {
+        let mut pin = self.pin.lock().unwrap();
+        if brightness >= 1.0 {
             trace!("setting led high...");
+            pin.clear_pwm().unwrap();
             pin.set_high();
-            thread::sleep(Duration::from_millis(500));
+        } else if brightness <= 0.0 {
             trace!("setting led low...");
+            pin.clear_pwm().unwrap();
             pin.set_low();
-            thread::sleep(Duration::from_millis(500));
+        } else {
+            trace!("setting led duty cycle to {:.2}...", brightness);
+            pin.set_pwm_frequency(PWM_FREQUENCY, brightness).unwrap();
         }
     }
 }
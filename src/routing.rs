@@ -0,0 +1,206 @@
+use crate::message_handler::EventKind;
+use serde::Deserialize;
+
+/// Decides what happens to the events matching every condition it sets.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct RoutingRule {
+    /// Glob on the `owner/name` of the repository, like `manuelarte/*`.
+    repository: Option<String>,
+    kind: Option<EventKind>,
+    /// The webhook `action`, like `created`.
+    action: Option<String>,
+    /// Glob on the login of the sender, like `*[bot]`.
+    sender: Option<String>,
+    /// Whether the event is ignored.
+    #[serde(default)]
+    drop: bool,
+    /// Pattern played instead of the one mapped to the event kind.
+    animation: Option<String>,
+    /// Events of a higher priority are played first, and dropped last when the queue is full.
+    #[serde(default)]
+    priority: u8,
+}
+
+impl RoutingRule {
+    fn matches(
+        &self,
+        repository: Option<&str>,
+        kind: EventKind,
+        action: Option<&str>,
+        sender: &str,
+    ) -> bool {
+        self.repository
+            .as_deref()
+            .is_none_or(|glob| repository.is_some_and(|r| glob_matches(glob, r)))
+            && self.kind.is_none_or(|k| k == kind)
+            && self.action.as_deref().is_none_or(|a| action == Some(a))
+            && self
+                .sender
+                .as_deref()
+                .is_none_or(|glob| glob_matches(glob, sender))
+    }
+
+    pub fn drops(&self) -> bool {
+        self.drop
+    }
+
+    pub fn animation(&self) -> Option<&str> {
+        self.animation.as_deref()
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+}
+
+/// Rules in the order they are tried, the first matching one applies.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct RoutingRules(Vec<RoutingRule>);
+
+impl RoutingRules {
+    /// The first rule matching the event, if any. A rule on repositories never matches
+    /// events without one, like sponsorships.
+    pub fn route(
+        &self,
+        repository: Option<&str>,
+        kind: EventKind,
+        action: Option<&str>,
+        sender: &str,
+    ) -> Option<&RoutingRule> {
+        self.0
+            .iter()
+            .find(|rule| rule.matches(repository, kind, action, sender))
+    }
+
+    pub fn animations(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(RoutingRule::animation)
+    }
+}
+
+/// Whether `text` matches `glob`, where `*` stands for any characters and `?` for one.
+/// Like GitHub names, the match is case insensitive.
+fn glob_matches(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut g, mut t) = (0, 0);
+    // position of the last `*` and of the text it is matched against, to backtrack to
+    let mut star = None;
+    while t < text.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, t));
+                g += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                g += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_g, star_t)) => {
+                    // let the `*` take one more character
+                    star = Some((star_g, star_t + 1));
+                    g = star_g + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str) -> RoutingRules {
+        #[derive(Deserialize)]
+        struct Settings {
+            routes: RoutingRules,
+        }
+        crate::settings_from_toml::<Settings>(toml).unwrap().routes
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_matches("manuelarte/*", "manuelarte/motivate-me"));
+        assert!(glob_matches("*/motivate-*", "ManuelArte/Motivate-Me"));
+        assert!(glob_matches("*[bot]", "dependabot[bot]"));
+        assert!(glob_matches("octo?at", "octocat"));
+        assert!(glob_matches("*a*b", "xaab"));
+        assert!(!glob_matches("manuelarte/*", "octocat/motivate-me"));
+        assert!(!glob_matches("octo?at", "octoat"));
+        assert!(!glob_matches("*[bot]", "dependabot"));
+    }
+
+    #[test]
+    fn first_matching_rule_applies() {
+        let rules = rules(
+            r#"
+            [[routes]]
+            sender = "*[bot]"
+            drop = true
+
+            [[routes]]
+            repository = "manuelarte/motivate-me"
+            kind = "release"
+            animation = "celebrate"
+            priority = 10
+
+            [[routes]]
+            repository = "manuelarte/*"
+            kind = "issue_opened"
+            action = "opened"
+            priority = 1
+            "#,
+        );
+        let bot = rules.route(
+            Some("manuelarte/motivate-me"),
+            EventKind::Release,
+            Some("published"),
+            "dependabot[bot]",
+        );
+        assert!(bot.is_some_and(RoutingRule::drops));
+
+        let release = rules
+            .route(
+                Some("manuelarte/motivate-me"),
+                EventKind::Release,
+                Some("published"),
+                "manuelarte",
+            )
+            .unwrap();
+        assert!(!release.drops());
+        assert_eq!(release.animation(), Some("celebrate"));
+        assert_eq!(release.priority(), 10);
+
+        let issue = rules.route(
+            Some("manuelarte/other"),
+            EventKind::IssueOpened,
+            Some("opened"),
+            "octocat",
+        );
+        assert_eq!(issue.map(RoutingRule::priority), Some(1));
+    }
+
+    #[test]
+    fn every_condition_must_match() {
+        let rules = rules(
+            r#"
+            [[routes]]
+            repository = "manuelarte/*"
+            kind = "issue_closed"
+            action = "closed"
+            drop = true
+            "#,
+        );
+        let route = |repository, kind, action| rules.route(repository, kind, action, "octocat");
+        assert!(route(Some("manuelarte/a"), EventKind::IssueClosed, Some("closed")).is_some());
+        assert!(route(Some("octocat/a"), EventKind::IssueClosed, Some("closed")).is_none());
+        assert!(route(Some("manuelarte/a"), EventKind::IssueOpened, Some("closed")).is_none());
+        assert!(route(Some("manuelarte/a"), EventKind::IssueClosed, Some("opened")).is_none());
+        assert!(route(Some("manuelarte/a"), EventKind::IssueClosed, None).is_none());
+        assert!(route(None, EventKind::IssueClosed, Some("closed")).is_none());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::AppConfig;
-use hmac::{Hmac, Mac};
+use hmac::{Hmac, KeyInit, Mac};
 use sha2::Sha256;
+use std::fmt;
 use std::fmt::Debug;
 use std::sync::Arc;
 use tracing::warn;
@@ -25,11 +26,17 @@ impl SignatureValidator for AlwaysTrueValidator {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Rsa256SignatureValidator {
     secret: String,
 }
 
+impl Debug for Rsa256SignatureValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redacted(f, "Rsa256SignatureValidator", "secret")
+    }
+}
+
 impl Rsa256SignatureValidator {
     pub fn new(secret: &str) -> Self {
         Self {
@@ -40,16 +47,88 @@ impl Rsa256SignatureValidator {
 
 impl SignatureValidator for Rsa256SignatureValidator {
     fn validate(&self, payload: &[u8], expected_signature: &str) -> bool {
-        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).unwrap();
-        mac.update(payload);
+        // GitHub sends the signature as `sha256=<hex digest>`.
+        let Some(expected_hex) = expected_signature.strip_prefix("sha256=") else {
+            return false;
+        };
+        verify_hmac_sha256(&self.secret, payload, expected_hex)
+    }
+}
+
+/// Gitea and Forgejo send the hex digest alone, without the `sha256=` prefix.
+#[derive(Clone)]
+pub struct GiteaSignatureValidator {
+    secret: String,
+}
+
+impl Debug for GiteaSignatureValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redacted(f, "GiteaSignatureValidator", "secret")
+    }
+}
+
+impl GiteaSignatureValidator {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            secret: secret.to_string(),
+        }
+    }
+}
+
+impl SignatureValidator for GiteaSignatureValidator {
+    fn validate(&self, payload: &[u8], expected_signature: &str) -> bool {
+        verify_hmac_sha256(&self.secret, payload, expected_signature)
+    }
+}
+
+fn verify_hmac_sha256(secret: &str, payload: &[u8], expected_hex: &str) -> bool {
+    let Ok(expected) = hex::decode(expected_hex) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(payload);
+
+    // `verify_slice` compares in constant time.
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Checks a secret token sent as is, like GitLab's `X-Gitlab-Token` or a bearer token. The
+/// payload is not signed.
+#[derive(Clone)]
+pub struct TokenValidator {
+    token: String,
+}
+
+impl Debug for TokenValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        redacted(f, "TokenValidator", "token")
+    }
+}
+
+impl TokenValidator {
+    pub fn new(token: &str) -> Self {
+        Self {
+            token: token.to_string(),
+        }
+    }
+}
 
-        let result = mac.finalize();
-        let expected = result.into_bytes();
-        let expected_hex = hex::encode(expected);
-        expected_hex.eq(expected_signature)
+impl SignatureValidator for TokenValidator {
+    fn validate(&self, _: &[u8], expected_signature: &str) -> bool {
+        constant_time_eq(self.token.as_bytes(), expected_signature.as_bytes())
     }
 }
 
+/// Debug output without the secret, validators are logged along with the app state.
+fn redacted(f: &mut fmt::Formatter<'_>, name: &str, field: &str) -> fmt::Result {
+    f.debug_struct(name).field(field, &"<redacted>").finish()
+}
+
+/// Compares without returning early, so the time taken doesn't tell how much matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 pub fn get_signature_validator(cfg: &AppConfig) -> Arc<dyn SignatureValidator> {
     match cfg.environment.as_str() {
         "production" => Arc::new(Rsa256SignatureValidator::new(cfg.secret.as_str())),
@@ -57,6 +136,49 @@ pub fn get_signature_validator(cfg: &AppConfig) -> Arc<dyn SignatureValidator> {
     }
 }
 
+/// Validator of `X-Gitlab-Token`, `gitlab_token` falls back to `secret`.
+pub fn get_gitlab_token_validator(cfg: &AppConfig) -> Arc<dyn SignatureValidator> {
+    match cfg.environment.as_str() {
+        "production" => Arc::new(TokenValidator::new(
+            cfg.gitlab_token.as_deref().unwrap_or(cfg.secret.as_str()),
+        )),
+        _ => Arc::new(AlwaysTrueValidator::new()),
+    }
+}
+
+/// Validator of `X-Gitea-Signature`, `gitea_secret` falls back to `secret`.
+pub fn get_gitea_signature_validator(cfg: &AppConfig) -> Arc<dyn SignatureValidator> {
+    match cfg.environment.as_str() {
+        "production" => Arc::new(GiteaSignatureValidator::new(
+            cfg.gitea_secret.as_deref().unwrap_or(cfg.secret.as_str()),
+        )),
+        _ => Arc::new(AlwaysTrueValidator::new()),
+    }
+}
+
+/// Validator of Bitbucket's `X-Hub-Signature`, signed like GitHub's with `bitbucket_secret`,
+/// or `secret` when not set.
+pub fn get_bitbucket_signature_validator(cfg: &AppConfig) -> Arc<dyn SignatureValidator> {
+    match cfg.environment.as_str() {
+        "production" => Arc::new(Rsa256SignatureValidator::new(
+            cfg.bitbucket_secret
+                .as_deref()
+                .unwrap_or(cfg.secret.as_str()),
+        )),
+        _ => Arc::new(AlwaysTrueValidator::new()),
+    }
+}
+
+/// Validator of the `/motivate` bearer token, `motivate_token` falls back to `secret`.
+pub fn get_motivate_token_validator(cfg: &AppConfig) -> Arc<dyn SignatureValidator> {
+    match cfg.environment.as_str() {
+        "production" => Arc::new(TokenValidator::new(
+            cfg.motivate_token.as_deref().unwrap_or(cfg.secret.as_str()),
+        )),
+        _ => Arc::new(AlwaysTrueValidator::new()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,8 +186,53 @@ mod tests {
     #[test]
     fn signature_test() {
         let signature_validator = Rsa256SignatureValidator::new("It's a Secret to Everybody");
-        let expected_signature = "757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        let expected_signature =
+            "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
         let result = signature_validator.validate("Hello, World!".as_bytes(), expected_signature);
         assert!(result);
     }
+
+    #[test]
+    fn signature_without_prefix_test() {
+        let signature_validator = Rsa256SignatureValidator::new("It's a Secret to Everybody");
+        let expected_signature = "757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        let result = signature_validator.validate("Hello, World!".as_bytes(), expected_signature);
+        assert!(!result);
+    }
+
+    #[test]
+    fn signature_not_hex_test() {
+        let signature_validator = Rsa256SignatureValidator::new("It's a Secret to Everybody");
+        let result = signature_validator.validate("Hello, World!".as_bytes(), "sha256=zzzz");
+        assert!(!result);
+    }
+
+    #[test]
+    fn token_test() {
+        let validator = TokenValidator::new("It's a Secret to Everybody");
+        assert!(validator.validate(b"{}", "It's a Secret to Everybody"));
+        assert!(!validator.validate(b"{}", "It's a Secret to Everybod"));
+        assert!(!validator.validate(b"{}", "It's a secret to Everybody"));
+    }
+
+    #[test]
+    fn gitea_signature_test() {
+        let validator = GiteaSignatureValidator::new("It's a Secret to Everybody");
+        let signature = "757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(validator.validate("Hello, World!".as_bytes(), signature));
+        assert!(!validator.validate("Hello, World!".as_bytes(), &format!("sha256={signature}")));
+    }
+
+    #[test]
+    fn secrets_are_not_debugged() {
+        let secret = "It's a Secret to Everybody";
+        let debugged = format!(
+            "{:?} {:?} {:?}",
+            Rsa256SignatureValidator::new(secret),
+            GiteaSignatureValidator::new(secret),
+            TokenValidator::new(secret)
+        );
+        assert!(!debugged.contains(secret));
+        assert!(debugged.contains(r#"TokenValidator { token: "<redacted>" }"#));
+    }
 }
@@ -0,0 +1,67 @@
+use serde::Deserialize;
+
+/// Animation played for sponsorships of at least `min_monthly_dollars`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SponsorshipThreshold {
+    min_monthly_dollars: u32,
+    animation: String,
+}
+
+/// Picks how intense the sponsorship animation is from the tier amount.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct SponsorshipTiers {
+    thresholds: Vec<SponsorshipThreshold>,
+}
+
+impl SponsorshipTiers {
+    /// The animation of the highest threshold reached by `dollars`, if any.
+    pub fn animation_for(&self, dollars: u32) -> Option<&str> {
+        self.thresholds
+            .iter()
+            .filter(|threshold| threshold.min_monthly_dollars <= dollars)
+            .max_by_key(|threshold| threshold.min_monthly_dollars)
+            .map(|threshold| threshold.animation.as_str())
+    }
+
+    /// Every animation name used by a threshold.
+    pub fn animations(&self) -> impl Iterator<Item = &str> {
+        self.thresholds
+            .iter()
+            .map(|threshold| threshold.animation.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings_from_toml;
+
+    #[test]
+    fn highest_reached_threshold_is_used() {
+        let toml = r#"
+        [[sponsorship_tiers]]
+        min_monthly_dollars = 100
+        animation = "huge"
+
+        [[sponsorship_tiers]]
+        min_monthly_dollars = 5
+        animation = "small"
+
+        [[sponsorship_tiers]]
+        min_monthly_dollars = 25
+        animation = "big"
+        "#;
+        #[derive(Deserialize)]
+        struct Settings {
+            sponsorship_tiers: SponsorshipTiers,
+        }
+        let tiers = settings_from_toml::<Settings>(toml)
+            .unwrap()
+            .sponsorship_tiers;
+        assert_eq!(tiers.animation_for(1), None);
+        assert_eq!(tiers.animation_for(5), Some("small"));
+        assert_eq!(tiers.animation_for(99), Some("big"));
+        assert_eq!(tiers.animation_for(500), Some("huge"));
+    }
+}